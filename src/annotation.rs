@@ -0,0 +1,490 @@
+//The GPLv3 License (GPLv3)
+//
+//Copyright (c) 2023 Ciubix8513
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::eval::parse_integer;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sign {
+    Unsigned,
+    Signed,
+    //The position of the sign bit
+    Bit(u32),
+}
+
+//Which bit the scale is the price of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScaleKind {
+    //ЦМР, the least significant bit
+    Lsb,
+    //ЦСР, the most significant bit
+    Msb,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scale {
+    pub kind: ScaleKind,
+    pub value: f64,
+}
+
+//`bits:5..19`, the bounds are kept in the order they were written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitRange {
+    pub first: u32,
+    pub last: u32,
+}
+
+//How the value of a key is read
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueKind {
+    #[default]
+    Text,
+    Integer,
+    Number,
+    //`0..100`
+    Range,
+    //`yes`/`no` or the position of the sign bit
+    Sign,
+    Bits,
+    Lsb,
+    Msb,
+}
+
+//The table column a key ends up in
+#[derive(Deserialize, Default, Debug, Clone, PartialEq, Eq)]
+#[serde(from = "String")]
+pub enum Column {
+    //ЦСР (ЦМР)
+    Scale,
+    //Знак
+    Sign,
+    //Размещение в разряде
+    Bits,
+    //Примечание
+    #[default]
+    Remarks,
+    //A column of its own with this header
+    Custom(String),
+}
+
+impl ValueKind {
+    //The name used in the config file
+    pub fn name(&self) -> &'static str {
+        match self {
+            ValueKind::Text => "text",
+            ValueKind::Integer => "integer",
+            ValueKind::Number => "number",
+            ValueKind::Range => "range",
+            ValueKind::Sign => "sign",
+            ValueKind::Bits => "bits",
+            ValueKind::Lsb => "lsb",
+            ValueKind::Msb => "msb",
+        }
+    }
+}
+
+impl From<String> for Column {
+    fn from(name: String) -> Column {
+        match name.as_str() {
+            "scale" => Column::Scale,
+            "sign" => Column::Sign,
+            "bits" => Column::Bits,
+            "remarks" => Column::Remarks,
+            _ => Column::Custom(name),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Key {
+    #[serde(rename = "type", default)]
+    pub kind: ValueKind,
+    #[serde(default)]
+    pub column: Column,
+}
+
+//The known annotation keys, the config file can add more or redefine these
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Grammar {
+    pub keys: HashMap<String, Key>,
+}
+
+impl Default for Grammar {
+    fn default() -> Grammar {
+        let key = |kind, column| Key { kind, column };
+        Grammar {
+            keys: HashMap::from([
+                ("min".to_owned(), key(ValueKind::Number, Column::Remarks)),
+                ("max".to_owned(), key(ValueKind::Number, Column::Remarks)),
+                ("signed".to_owned(), key(ValueKind::Sign, Column::Sign)),
+                ("bits".to_owned(), key(ValueKind::Bits, Column::Bits)),
+                ("lsb".to_owned(), key(ValueKind::Lsb, Column::Scale)),
+                ("msb".to_owned(), key(ValueKind::Msb, Column::Scale)),
+            ]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Text(String),
+    Integer(i64),
+    Number(f64),
+    Range(f64, f64),
+    Sign(Sign),
+    Bits(BitRange),
+    Scale(Scale),
+}
+
+//A single `key:value` of the annotation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub key: String,
+    //The value as written
+    pub raw: String,
+    //Text if the key is unknown or the value doesn't match the type of the key
+    pub value: Value,
+    pub column: Column,
+    //The key is known, but the value doesn't match its type or has words after it
+    pub malformed: bool,
+}
+
+//The typed contents of the `// (min:0 max:100 signed:4 bits:5..19 lsb:0.001)` part of a field comment
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub sign: Option<Sign>,
+    pub bits: Option<BitRange>,
+    pub scale: Option<Scale>,
+    //Every `key:value` in the order it was written, unknown keys included
+    pub fields: Vec<Field>,
+    //Whatever came before the first key
+    pub text: String,
+}
+
+impl Annotation {
+    //Splits the annotation into `key:value` pairs, in any order, a value may contain spaces
+    pub fn parse(text: &str, grammar: &Grammar) -> Annotation {
+        let mut annotation = Annotation::default();
+        let mut text_words = Vec::new();
+        let mut pairs: Vec<(&str, Vec<&str>)> = Vec::new();
+        for word in text.split_whitespace() {
+            match key_of(word) {
+                Some((key, value)) => pairs.push((key, vec![value])),
+                None => match pairs.last_mut() {
+                    Some((_, value)) => value.push(word),
+                    None => text_words.push(word),
+                },
+            }
+        }
+        annotation.text = text_words.join(" ");
+
+        for (key, value) in pairs {
+            let words = value
+                .into_iter()
+                .filter(|w| !w.is_empty())
+                .collect::<Vec<_>>();
+            let raw = words.join(" ");
+            let (value, column) = match grammar.keys.get(key) {
+                //Only text can span words, anything after a typed value is a mistake
+                Some(known) if known.kind != ValueKind::Text && words.len() > 1 => {
+                    (None, known.column.clone())
+                }
+                Some(known) => (Value::parse(known.kind, &raw), known.column.clone()),
+                None => (Some(Value::Text(raw.clone())), Column::Remarks),
+            };
+            let malformed = value.is_none();
+            let value = value.unwrap_or_else(|| Value::Text(raw.clone()));
+            match value {
+                Value::Sign(sign) => annotation.sign = Some(sign),
+                Value::Bits(bits) => annotation.bits = Some(bits),
+                Value::Scale(scale) => annotation.scale = Some(scale),
+                _ => {}
+            }
+            annotation.fields.push(Field {
+                key: key.to_owned(),
+                raw,
+                value,
+                column,
+                malformed,
+            });
+        }
+        annotation
+    }
+
+    //The raw values going to the column, joined
+    pub fn column_text(&self, column: &Column) -> Option<String> {
+        let values = self
+            .fields
+            .iter()
+            .filter(|f| &f.column == column)
+            .map(|f| match column {
+                Column::Remarks => format!("{}:{}", f.key, f.raw),
+                _ => f.raw.clone(),
+            })
+            .collect::<Vec<_>>();
+        (!values.is_empty()).then(|| values.join(" "))
+    }
+
+    //The free text and the fields without a column of their own, the way they were written
+    pub fn remarks(&self) -> String {
+        let fields = self.column_text(&Column::Remarks).unwrap_or_default();
+        match (self.text.is_empty(), fields.is_empty()) {
+            (false, false) => format!("{} {}", self.text, fields),
+            (false, true) => self.text.clone(),
+            _ => fields,
+        }
+    }
+
+    //The number of bits carrying the magnitude, the sign bit excluded
+    pub fn value_bits(&self) -> Option<u32> {
+        let bits = self.bits?;
+        let width = bits.width();
+        Some(match self.sign {
+            Some(Sign::Signed) => width - 1,
+            Some(Sign::Bit(bit)) if bits.contains(bit) => width - 1,
+            _ => width,
+        })
+    }
+
+    //The price of the least significant bit, converted from the MSB if needed
+    pub fn lsb(&self) -> Option<f64> {
+        let scale = self.scale?;
+        match scale.kind {
+            ScaleKind::Lsb => Some(scale.value),
+            ScaleKind::Msb => {
+                Some(scale.value / 2f64.powi(self.value_bits()?.checked_sub(1)? as i32))
+            }
+        }
+    }
+
+    //The price of the most significant bit, converted from the LSB if needed
+    pub fn msb(&self) -> Option<f64> {
+        let scale = self.scale?;
+        match scale.kind {
+            ScaleKind::Msb => Some(scale.value),
+            ScaleKind::Lsb => {
+                Some(scale.value * 2f64.powi(self.value_bits()?.checked_sub(1)? as i32))
+            }
+        }
+    }
+}
+
+impl Value {
+    fn parse(kind: ValueKind, text: &str) -> Option<Value> {
+        Some(match kind {
+            ValueKind::Text => Value::Text(text.to_owned()),
+            ValueKind::Integer => Value::Integer(match text.strip_prefix('-') {
+                Some(text) => parse_integer(text)?.checked_neg()?,
+                None => parse_integer(text.trim_start_matches('+'))?,
+            }),
+            ValueKind::Number => Value::Number(text.parse().ok()?),
+            ValueKind::Range => {
+                let (from, to) = text.split_once("..")?;
+                Value::Range(from.trim().parse().ok()?, to.trim().parse().ok()?)
+            }
+            ValueKind::Sign => Value::Sign(Sign::parse(text)?),
+            ValueKind::Bits => Value::Bits(BitRange::parse(text)?),
+            ValueKind::Lsb => Value::Scale(Scale::parse(ScaleKind::Lsb, text)?),
+            ValueKind::Msb => Value::Scale(Scale::parse(ScaleKind::Msb, text)?),
+        })
+    }
+}
+
+//`key:value` or `key:` when the value is the next word
+fn key_of(word: &str) -> Option<(&str, &str)> {
+    let (key, value) = word.split_once(':')?;
+    (key.starts_with(|c: char| c.is_alphabetic())
+        && key
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-'))
+    .then_some((key, value))
+}
+
+impl Sign {
+    //`yes`/`no`, `true`/`false` or `signed`/`unsigned`, a number is always the position of the sign bit
+    pub fn parse(text: &str) -> Option<Sign> {
+        match text.to_lowercase().as_str() {
+            "yes" | "true" | "signed" | "да" => Some(Sign::Signed),
+            "no" | "false" | "unsigned" | "нет" => Some(Sign::Unsigned),
+            text => text.parse().ok().map(Sign::Bit),
+        }
+    }
+}
+
+impl BitRange {
+    //`5..19` or a single bit, `7`
+    pub fn parse(text: &str) -> Option<BitRange> {
+        let (first, last) = text.split_once("..").unwrap_or((text, text));
+        Some(BitRange {
+            first: first.trim().parse().ok()?,
+            last: last.trim().parse().ok()?,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.first.abs_diff(self.last) + 1
+    }
+
+    pub fn contains(&self, bit: u32) -> bool {
+        (self.first.min(self.last)..=self.first.max(self.last)).contains(&bit)
+    }
+}
+
+impl Scale {
+    //Only the leading number is used, so trailing units or quotes don't matter
+    pub fn parse(kind: ScaleKind, text: &str) -> Option<Scale> {
+        let end = text
+            .find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E')))
+            .unwrap_or(text.len());
+        Some(Scale {
+            kind,
+            value: text[..end].parse().ok()?,
+        })
+    }
+}
+
+#[test]
+fn parse_annotation_test() {
+    let grammar = Grammar::default();
+    let annotation = Annotation::parse(
+        "min:-324000 max:324000 signed:4 bits:5..19 lsb:0.001\"",
+        &grammar,
+    );
+    assert_eq!(annotation.sign, Some(Sign::Bit(4)));
+    assert_eq!(annotation.bits, Some(BitRange { first: 5, last: 19 }));
+    assert_eq!(
+        annotation.scale,
+        Some(Scale {
+            kind: ScaleKind::Lsb,
+            value: 0.001
+        })
+    );
+    assert_eq!(annotation.fields[0].value, Value::Number(-324000.0));
+    //The sign bit is outside of the range, so all 15 bits are the value
+    assert_eq!(annotation.value_bits(), Some(15));
+    assert_eq!(annotation.remarks(), "min:-324000 max:324000");
+
+    let annotation = Annotation::parse("signed:yes bits:0..7 msb:64", &grammar);
+    assert_eq!(annotation.sign, Some(Sign::Signed));
+    assert_eq!(annotation.scale.unwrap().kind, ScaleKind::Msb);
+    assert_eq!(annotation.remarks(), "");
+    assert_eq!(
+        Annotation::parse("signed:FALSE", &grammar).sign,
+        Some(Sign::Unsigned)
+    );
+    assert_eq!(
+        Annotation::parse("signed:unsigned", &grammar).sign,
+        Some(Sign::Unsigned)
+    );
+    //Numbers are sign bit positions, the first two bits included
+    assert_eq!(
+        Annotation::parse("signed:0", &grammar).sign,
+        Some(Sign::Bit(0))
+    );
+    assert_eq!(
+        Annotation::parse("signed:1", &grammar).sign,
+        Some(Sign::Bit(1))
+    );
+    assert_eq!(
+        Annotation::parse("signed:Нет", &grammar).sign,
+        Some(Sign::Unsigned)
+    );
+}
+
+#[test]
+fn parse_any_order_test() {
+    let grammar = Grammar::default();
+    let annotation = Annotation::parse("lsb:0.5 max: 100 bits:0..7 min: 0", &grammar);
+    assert_eq!(annotation.bits, Some(BitRange { first: 0, last: 7 }));
+    assert_eq!(annotation.msb(), Some(64.0));
+    assert_eq!(annotation.remarks(), "max:100 min:0");
+
+    //Free text and unknown keys are kept
+    let annotation = Annotation::parse("see ICD unit: m/s endian:big", &grammar);
+    assert_eq!(annotation.text, "see ICD");
+    assert_eq!(annotation.fields.len(), 2);
+    assert_eq!(annotation.fields[0].value, Value::Text("m/s".to_owned()));
+    assert_eq!(annotation.remarks(), "see ICD unit:m/s endian:big");
+    assert_eq!(Annotation::parse("100,200", &grammar).remarks(), "100,200");
+}
+
+#[test]
+fn custom_grammar_test() {
+    let mut grammar = Grammar::default();
+    grammar.keys.insert(
+        "unit".to_owned(),
+        Key {
+            kind: ValueKind::Text,
+            column: Column::from("Единицы".to_owned()),
+        },
+    );
+    grammar.keys.insert(
+        "default".to_owned(),
+        Key {
+            kind: ValueKind::Integer,
+            column: Column::Remarks,
+        },
+    );
+    grammar.keys.insert(
+        "range".to_owned(),
+        Key {
+            kind: ValueKind::Range,
+            column: Column::Remarks,
+        },
+    );
+    let annotation = Annotation::parse("unit:m default:0x10 range:-5..5 default:x", &grammar);
+    assert_eq!(
+        annotation.column_text(&Column::Custom("Единицы".to_owned())),
+        Some("m".to_owned())
+    );
+    assert_eq!(annotation.fields[1].value, Value::Integer(16));
+    assert_eq!(annotation.fields[2].value, Value::Range(-5.0, 5.0));
+    //Doesn't match the type, so it stays text
+    assert_eq!(annotation.fields[3].value, Value::Text("x".to_owned()));
+    assert!(annotation.fields[3].malformed);
+    assert!(!annotation.fields[1].malformed);
+
+    //Free words don't silently become part of a typed value
+    let annotation = Annotation::parse("bits:5..9 words lsb:0.5 volts unit: m s", &grammar);
+    assert_eq!(annotation.bits, None);
+    assert!(annotation.fields[0].malformed);
+    assert_eq!(annotation.fields[0].raw, "5..9 words");
+    assert_eq!(annotation.scale, None);
+    assert!(annotation.fields[1].malformed);
+    assert_eq!(annotation.fields[2].value, Value::Text("m s".to_owned()));
+    assert!(!annotation.fields[2].malformed);
+}
+
+#[test]
+fn convert_scale_test() {
+    let grammar = Grammar::default();
+    let annotation = Annotation::parse("signed:no bits:0..7 lsb:0.5", &grammar);
+    assert_eq!(annotation.lsb(), Some(0.5));
+    assert_eq!(annotation.msb(), Some(64.0));
+
+    let annotation = Annotation::parse("signed:true bits:0..7 msb:64", &grammar);
+    assert_eq!(annotation.msb(), Some(64.0));
+    assert_eq!(annotation.lsb(), Some(1.0));
+
+    //Without the width there's nothing to convert with
+    let annotation = Annotation::parse("lsb:0.5", &grammar);
+    assert_eq!(annotation.lsb(), Some(0.5));
+    assert_eq!(annotation.msb(), None);
+}
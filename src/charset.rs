@@ -0,0 +1,231 @@
+//The GPLv3 License (GPLv3)
+//
+//Copyright (c) 2023 Ciubix8513
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use encoding::{
+    all::{IBM866, KOI8_R, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1251},
+    DecoderTrap, Encoding, EncodingRef, RawDecoder, RawEncoder,
+};
+use std::{borrow::Cow, fmt};
+
+//Goes into the encoding lists, every file is then decoded with the encoding `detect` picks for it
+pub struct AutoEncoding;
+
+pub static AUTO: &AutoEncoding = &AutoEncoding;
+
+impl Encoding for AutoEncoding {
+    fn name(&self) -> &'static str {
+        "auto"
+    }
+
+    fn raw_encoder(&self) -> Box<dyn RawEncoder> {
+        UTF_8.raw_encoder()
+    }
+
+    //Detection needs the whole input, a decoder that gets it in parts can only assume UTF-8
+    fn raw_decoder(&self) -> Box<dyn RawDecoder> {
+        UTF_8.raw_decoder()
+    }
+
+    fn decode(&self, input: &[u8], trap: DecoderTrap) -> Result<String, Cow<'static, str>> {
+        let (encoding, bom) = detect(input);
+        encoding.decode(&input[bom..], trap)
+    }
+}
+
+//`auto` and everything the encoding crate knows, for looking the encodings up by name
+pub fn encodings() -> Vec<EncodingRef> {
+    std::iter::once(AUTO as EncodingRef)
+        .chain(encoding::all::encodings().iter().copied())
+        .filter(|e| e.name() != "error")
+        .collect()
+}
+
+//A byte sequence the encoding has no characters for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidBytes {
+    //From the start of the file, the BOM included
+    pub offset: usize,
+    pub bytes: Vec<u8>,
+    //1-based, the column is in bytes, the characters around can't be trusted
+    pub line: usize,
+    pub column: usize,
+}
+
+impl InvalidBytes {
+    //`FF 80`
+    pub fn hex(&self) -> String {
+        let bytes = self
+            .bytes
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<_>>();
+        bytes.join(" ")
+    }
+}
+
+//`FF 80` at byte 35 (line 2, column 12)
+impl fmt::Display for InvalidBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` at byte {} (line {}, column {})",
+            self.hex(),
+            self.offset,
+            self.line,
+            self.column
+        )
+    }
+}
+
+//Decodes the input after the BOM the way `DecoderTrap::Ignore` does, but keeps what was dropped
+pub fn decode(input: &[u8], bom: usize, encoding: &dyn Encoding) -> (String, Vec<InvalidBytes>) {
+    let mut decoder = encoding.raw_decoder();
+    let mut output = String::new();
+    let mut invalid = Vec::new();
+    let mut remaining = bom;
+    loop {
+        let (processed, error) = decoder.raw_feed(&input[remaining..], &mut output);
+        let start = remaining + processed;
+        match error {
+            //`upto` is where decoding goes on from, relative to what was fed
+            Some(error) => remaining = (remaining as isize + error.upto) as usize,
+            //An incomplete sequence at the end of the input
+            None => {
+                if decoder.raw_finish(&mut output).is_some() {
+                    invalid.push(invalid_bytes(input, start, input.len()));
+                }
+                break;
+            }
+        }
+        //Neighbouring bytes are reported together
+        match invalid.last_mut() {
+            Some(last) if last.offset + last.bytes.len() == start => {
+                last.bytes.extend_from_slice(&input[start..remaining])
+            }
+            _ => invalid.push(invalid_bytes(input, start, remaining)),
+        }
+    }
+    (output, invalid)
+}
+
+fn invalid_bytes(input: &[u8], start: usize, end: usize) -> InvalidBytes {
+    let line_start = input[..start]
+        .iter()
+        .rposition(|b| *b == b'\n')
+        .map_or(0, |i| i + 1);
+    InvalidBytes {
+        offset: start,
+        bytes: input[start..end].to_vec(),
+        line: input[..start].iter().filter(|b| **b == b'\n').count() + 1,
+        column: start - line_start + 1,
+    }
+}
+
+//The encoding of the bytes and the length of the BOM they start with
+pub fn detect(bytes: &[u8]) -> (EncodingRef, usize) {
+    if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        return (UTF_8, 3);
+    }
+    if bytes.starts_with(&[0xFF, 0xFE]) {
+        return (UTF_16LE, 2);
+    }
+    if bytes.starts_with(&[0xFE, 0xFF]) {
+        return (UTF_16BE, 2);
+    }
+    //Random single byte text is almost never valid UTF-8, this includes plain ASCII
+    if std::str::from_utf8(bytes).is_ok() {
+        return (UTF_8, 0);
+    }
+    //The first one wins a tie, it's the most common one
+    let mut best = (WINDOWS_1251, i64::MIN);
+    for candidate in [WINDOWS_1251, KOI8_R, IBM866] {
+        let text = candidate
+            .decode(bytes, DecoderTrap::Replace)
+            .unwrap_or_default();
+        let score = cyrillic_score(&text);
+        if score > best.1 {
+            best = (candidate, score);
+        }
+    }
+    (best.0, 0)
+}
+
+//The wrong code page turns russian text into a mix of capitals, rare letters and pseudographics
+fn cyrillic_score(text: &str) -> i64 {
+    let is_cyrillic = |c: char| ('\u{0400}'..='\u{04FF}').contains(&c);
+    let mut score = 0;
+    let mut previous = ' ';
+    for c in text.chars() {
+        if is_cyrillic(c) {
+            if c.is_lowercase() {
+                score += 1;
+                if "оеаинтсрвл".contains(c) {
+                    score += 1;
+                }
+            //Capitals are rare after a lowercase letter of the same word
+            } else if is_cyrillic(previous) && previous.is_lowercase() {
+                score -= 2;
+            }
+        } else if !c.is_ascii() {
+            score -= 2;
+        }
+        previous = c;
+    }
+    score
+}
+
+#[test]
+fn detect_bom_and_utf8_test() {
+    assert_eq!(detect(b"\xEF\xBB\xBFint a;").0.name(), "utf-8");
+    assert_eq!(detect(b"\xEF\xBB\xBFint a;").1, 3);
+    assert_eq!(detect(b"\xFF\xFEi\0").0.name(), "utf-16le");
+    assert_eq!(detect(b"int a;").0.name(), "utf-8");
+    assert_eq!(detect("//!< Длина".as_bytes()).0.name(), "utf-8");
+    assert_eq!(
+        AUTO.decode(b"\xEF\xBB\xBFa", DecoderTrap::Strict).unwrap(),
+        "a"
+    );
+}
+
+#[test]
+fn detect_code_page_test() {
+    let text = "uint16_t length; //!< Длина сообщения в словах (signed:нет)";
+    for encoding in [WINDOWS_1251, KOI8_R, IBM866] {
+        let bytes = encoding
+            .encode(text, encoding::EncoderTrap::Strict)
+            .unwrap();
+        assert_eq!(detect(&bytes).0.name(), encoding.name());
+        assert_eq!(AUTO.decode(&bytes, DecoderTrap::Strict).unwrap(), text);
+    }
+}
+
+#[test]
+fn decode_invalid_bytes_test() {
+    let input = b"\xEF\xBB\xBFint a;\n  int \xFF\xFEb; //!< \xD0";
+    let (text, invalid) = decode(input, 3, UTF_8);
+    assert_eq!(text, "int a;\n  int b; //!< ");
+    assert_eq!(invalid.len(), 2);
+    assert_eq!(invalid[0].offset, 16);
+    assert_eq!((invalid[0].line, invalid[0].column), (2, 7));
+    assert_eq!(
+        invalid[0].to_string(),
+        "`FF FE` at byte 16 (line 2, column 7)"
+    );
+    //Cut off at the end
+    assert_eq!(invalid[1].bytes, vec![0xD0]);
+    assert!(decode(b"int a;", 0, WINDOWS_1251).1.is_empty());
+}
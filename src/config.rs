@@ -0,0 +1,233 @@
+//The GPLv3 License (GPLv3)
+//
+//Copyright (c) 2023 Ciubix8513
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    annotation::Grammar,
+    layout::{Abi, TypeLayout, PRESETS},
+};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+//Everything that changes how the headers are read, loaded from a toml file:
+//
+//[annotation.keys.unit]
+//type = "text"
+//column = "Единицы измерения"
+//
+//[preprocessor]
+//defines = ["VARIANT=2", "LEGACY_PROTOCOL"]
+//include_paths = ["include", "../common"]
+//
+//[export]
+//locations = true
+//
+//[decoding]
+//strict = true
+//
+//[layout]
+//abi = "arm-eabi"
+//
+//[layout.types]
+//uint24_t = { size = 3, align = 1 }
+#[derive(Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct Config {
+    pub annotation: Grammar,
+    pub preprocessor: PreprocessorConfig,
+    pub export: ExportConfig,
+    pub decoding: DecodingConfig,
+    pub layout: LayoutConfig,
+}
+
+//The target the struct layouts are computed for
+#[derive(Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct LayoutConfig {
+    //One of the presets, no layouts are computed without it
+    pub abi: Option<String>,
+    //Types the preset doesn't know or gets wrong, `pointer` and `enum` are the pointers and the enums
+    pub types: HashMap<String, TypeLayout>,
+}
+
+impl LayoutConfig {
+    pub fn abi(&self) -> Result<Option<Abi>, String> {
+        let Some(name) = &self.abi else {
+            return Ok(None);
+        };
+        let mut abi = Abi::preset(name).ok_or_else(|| {
+            format!(
+                "Unknown ABI {}, expected one of {}",
+                name,
+                PRESETS.join(", ")
+            )
+        })?;
+        for (name, layout) in &self.types {
+            abi.set(name, *layout);
+        }
+        Ok(Some(abi))
+    }
+}
+
+//What to do with the bytes the encoding can't decode
+#[derive(Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct DecodingConfig {
+    //Fail the file instead of dropping the bytes with a warning
+    pub strict: bool,
+}
+
+//What goes into the document besides the documentation itself
+#[derive(Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct ExportConfig {
+    //Adds the header lines each table and row come from
+    pub locations: bool,
+}
+
+//The same thing as `-D` and `-U` of a compiler
+#[derive(Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct PreprocessorConfig {
+    //`NAME` or `NAME=VALUE`
+    pub defines: Vec<String>,
+    pub undefines: Vec<String>,
+    //Where `#include` looks for the headers, `-I`
+    pub include_paths: Vec<PathBuf>,
+}
+
+impl PreprocessorConfig {
+    //`NAME` alone is defined as 1, just like with `-D`
+    pub fn macros(&self) -> HashMap<String, String> {
+        self.defines
+            .iter()
+            .map(|define| match define.split_once('=') {
+                Some((name, value)) => (name.trim().to_owned(), value.trim().to_owned()),
+                None => (define.trim().to_owned(), "1".to_owned()),
+            })
+            .filter(|(name, _)| !self.undefines.contains(name))
+            .collect()
+    }
+
+    //The last of `define` and `undefine` for the same name wins
+    pub fn define(&mut self, define: &str) {
+        let name = define.split('=').next().unwrap_or_default().trim();
+        self.undefines.retain(|undefine| undefine != name);
+        self.defines.push(define.to_owned());
+    }
+
+    pub fn undefine(&mut self, name: &str) {
+        self.defines
+            .retain(|define| define.split('=').next().unwrap_or_default().trim() != name);
+        self.undefines.push(name.to_owned());
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Config, Error> {
+        Config::from_toml(&fs::read_to_string(path)?)
+    }
+
+    //The keys from the file are added to the default ones, replacing those with the same name
+    pub fn from_toml(text: &str) -> Result<Config, Error> {
+        let mut config: Config =
+            toml::from_str(text).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        let mut grammar = Grammar::default();
+        grammar.keys.extend(config.annotation.keys);
+        config.annotation = grammar;
+        config
+            .layout
+            .abi()
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        Ok(config)
+    }
+}
+
+#[test]
+fn load_config_test() {
+    use crate::annotation::{Column, ValueKind};
+    let config = Config::from_toml(
+        "[annotation.keys.unit]\n\
+         column = \"Единицы\"\n\
+         [annotation.keys.default]\n\
+         type = \"integer\"\n\
+         [annotation.keys.min]\n\
+         type = \"integer\"\n",
+    )
+    .unwrap();
+    let keys = &config.annotation.keys;
+    assert_eq!(keys["unit"].kind, ValueKind::Text);
+    assert_eq!(keys["unit"].column, Column::Custom("Единицы".to_owned()));
+    assert_eq!(keys["default"].column, Column::Remarks);
+    assert_eq!(keys["min"].kind, ValueKind::Integer);
+    //The defaults are still there
+    assert_eq!(keys["bits"].column, Column::Bits);
+
+    assert!(!config.export.locations);
+    assert!(
+        Config::from_toml("[export]\nlocations = true")
+            .unwrap()
+            .export
+            .locations
+    );
+
+    assert!(Config::from_toml("[annotation.keys.unit]\ntype = \"colour\"").is_err());
+}
+
+#[test]
+fn layout_config_test() {
+    let config = Config::from_toml(
+        "[layout]\n\
+         abi = \"dsp16\"\n\
+         [layout.types]\n\
+         uint24_t = { size = 3, align = 1 }\n\
+         pointer = { size = 4, align = 2 }\n",
+    )
+    .unwrap();
+    let abi = config.layout.abi().unwrap().unwrap();
+    assert_eq!(abi.types["int"], TypeLayout { size: 2, align: 2 });
+    assert_eq!(abi.types["int24_t"], TypeLayout { size: 3, align: 1 });
+    assert_eq!(abi.pointer, TypeLayout { size: 4, align: 2 });
+
+    assert!(Config::default().layout.abi().unwrap().is_none());
+    assert!(Config::from_toml("[layout]\nabi = \"pdp11\"").is_err());
+}
+
+#[test]
+fn preprocessor_config_test() {
+    let mut config = Config::from_toml(
+        "[preprocessor]\n\
+         defines = [\"VARIANT=2\", \"LEGACY\", \"DEBUG\"]\n\
+         undefines = [\"DEBUG\"]\n",
+    )
+    .unwrap();
+    let macros = config.preprocessor.macros();
+    assert_eq!(macros.len(), 2);
+    assert_eq!(macros["VARIANT"], "2");
+    assert_eq!(macros["LEGACY"], "1");
+
+    //Same as the command line, which comes after the file
+    config.preprocessor.define("DEBUG=0");
+    config.preprocessor.undefine("LEGACY");
+    let macros = config.preprocessor.macros();
+    assert_eq!(macros["DEBUG"], "0");
+    assert!(!macros.contains_key("LEGACY"));
+}
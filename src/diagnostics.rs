@@ -0,0 +1,93 @@
+//The GPLv3 License (GPLv3)
+//
+//Copyright (c) 2023 Ciubix8513
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::lexer::Span;
+use std::{fmt, path::PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    //Something is probably missing from the document
+    Warning,
+    //The code is broken, the parser had to guess
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    //Empty when the source didn't come from a file
+    pub file: PathBuf,
+    //1-based, like the spans
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Diagnostic {
+    pub(crate) fn warning(span: &Span, message: impl Into<String>) -> Diagnostic {
+        Diagnostic::new(Severity::Warning, span, message)
+    }
+
+    pub(crate) fn error(span: &Span, message: impl Into<String>) -> Diagnostic {
+        Diagnostic::new(Severity::Error, span, message)
+    }
+
+    fn new(severity: Severity, span: &Span, message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            severity,
+            message: message.into(),
+            file: PathBuf::new(),
+            line: span.line,
+            column: span.column,
+        }
+    }
+}
+
+//`file.h:3:5: warning: message`, the way compilers print them
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(
+            f,
+            "{}:{}:{}: {}: {}",
+            self.file.display(),
+            self.line,
+            self.column,
+            severity,
+            self.message
+        )
+    }
+}
+
+#[test]
+fn display_diagnostic_test() {
+    let span = Span {
+        start: 10,
+        end: 12,
+        line: 3,
+        column: 5,
+    };
+    let mut diagnostic = Diagnostic::warning(&span, "Field `a` is not documented");
+    diagnostic.file = PathBuf::from("test.h");
+    assert_eq!(
+        diagnostic.to_string(),
+        "test.h:3:5: warning: Field `a` is not documented"
+    );
+}
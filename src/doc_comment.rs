@@ -0,0 +1,431 @@
+//The GPLv3 License (GPLv3)
+//
+//Copyright (c) 2023 Ciubix8513
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//Commands that only change the formatting of the next word, the word itself is kept
+const INLINE_COMMANDS: [&str; 8] = ["a", "b", "c", "e", "em", "p", "ref", "link"];
+//Commands that take the rest of their line, the next line is a paragraph of its own
+const LINE_COMMANDS: [&str; 4] = ["defgroup", "addtogroup", "ingroup", "weakgroup"];
+//Commands that name the declaration the comment is for, the parser already knows it, so the line is dropped
+const DECLARATION_COMMANDS: [&str; 7] = ["struct", "union", "enum", "var", "fn", "def", "typedef"];
+//Commands that start a section of their own
+const SECTION_COMMANDS: [&str; 17] = [
+    "brief",
+    "short",
+    "details",
+    "note",
+    "remark",
+    "remarks",
+    "warning",
+    "attention",
+    "deprecated",
+    "param",
+    "return",
+    "returns",
+    "result",
+    "par",
+    "li",
+    "code",
+    "endcode",
+];
+//Commands kept as tags, anything else that looks like a command is just text, like `\0` or `\n`
+const TAG_COMMANDS: [&str; 20] = [
+    "since",
+    "see",
+    "sa",
+    "author",
+    "authors",
+    "version",
+    "date",
+    "copyright",
+    "todo",
+    "bug",
+    "test",
+    "pre",
+    "post",
+    "invariant",
+    "retval",
+    "throw",
+    "throws",
+    "exception",
+    "file",
+    "internal",
+];
+
+//The contents of a doc comment, split by the Doxygen commands
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct DocComment {
+    //`@brief`, or the first paragraph if there is no explicit brief
+    pub brief: String,
+    //Whether the brief was given with `@brief`
+    pub explicit_brief: bool,
+    //`@details` and any paragraphs after the brief
+    pub details: String,
+    //`@note`
+    pub notes: Vec<String>,
+    //`@warning` and `@attention`
+    pub warnings: Vec<String>,
+    //`@deprecated`, the text may be empty
+    pub deprecated: Option<String>,
+    //`@param [in] name text`
+    pub params: Vec<ParamDoc>,
+    //`@return` or `@returns`
+    pub returns: String,
+    //Any other command with its text, e.g. ("since", "1.2")
+    pub tags: Vec<(String, String)>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct ParamDoc {
+    pub name: String,
+    //`in`, `out` or `in,out`, if it was specified
+    pub direction: Option<String>,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Section {
+    Text,
+    Brief,
+    Details,
+    Note,
+    Warning,
+    Deprecated,
+    //The direction if it was attached to the command, `@param[in]`
+    Param(Option<String>),
+    Returns,
+    Tag(String),
+    //The title line of a `@par`, the paragraphs after it are details
+    Par,
+    //The rest of the line after `@struct name` and the like
+    Ignored,
+}
+
+impl DocComment {
+    pub fn parse(text: &str) -> DocComment {
+        let mut doc = DocComment::default();
+        let mut paragraphs = Vec::new();
+        let mut details = Vec::new();
+        let mut section = Section::Text;
+        let mut current = Vec::new();
+        //The lines of a `@code` block, kept as they are
+        let mut code: Option<Vec<&str>> = None;
+
+        for line in text.lines() {
+            if let Some(lines) = code.as_mut() {
+                if line
+                    .split_whitespace()
+                    .any(|w| command(w) == Some(("endcode", None)))
+                {
+                    details.push(lines.join("\n"));
+                    code = None;
+                } else {
+                    lines.push(line.trim_end());
+                }
+                continue;
+            }
+            //An empty line ends the paragraph and whatever command it belonged to
+            if line.trim().is_empty() {
+                doc.flush(&section, &mut current, &mut paragraphs, &mut details);
+                section = Section::Text;
+                continue;
+            }
+            for word in line.split_whitespace() {
+                match command(word) {
+                    Some((command, _)) if INLINE_COMMANDS.contains(&command) => {}
+                    Some(("endcode", _)) => {}
+                    //A list item is a paragraph of its own in the same section
+                    Some(("li", _)) => {
+                        doc.flush(&section, &mut current, &mut paragraphs, &mut details)
+                    }
+                    //The block starts on the next line, whatever follows `@code` on its line is dropped
+                    Some(("code", _)) => {
+                        doc.flush(&section, &mut current, &mut paragraphs, &mut details);
+                        //What follows the block belongs to the details with it
+                        section = Section::Details;
+                        code = Some(Vec::new());
+                        break;
+                    }
+                    Some((command, direction)) => {
+                        doc.flush(&section, &mut current, &mut paragraphs, &mut details);
+                        section = match command {
+                            "brief" | "short" => Section::Brief,
+                            "details" => Section::Details,
+                            "note" | "remark" | "remarks" => Section::Note,
+                            "warning" | "attention" => Section::Warning,
+                            "deprecated" => Section::Deprecated,
+                            "param" => Section::Param(direction.map(str::to_owned)),
+                            "return" | "returns" | "result" => Section::Returns,
+                            "par" => Section::Par,
+                            command if DECLARATION_COMMANDS.contains(&command) => Section::Ignored,
+                            _ => Section::Tag(command.to_owned()),
+                        };
+                        //Deprecated is allowed to be empty, so it's marked right away
+                        if section == Section::Deprecated && doc.deprecated.is_none() {
+                            doc.deprecated = Some(String::new());
+                        }
+                    }
+                    None => current.push(word),
+                }
+            }
+            if matches!(&section, Section::Tag(name) if LINE_COMMANDS.contains(&name.as_str())) {
+                doc.flush(&section, &mut current, &mut paragraphs, &mut details);
+                section = Section::Text;
+            }
+            if matches!(section, Section::Par | Section::Ignored) {
+                doc.flush(&section, &mut current, &mut paragraphs, &mut details);
+                section = match section {
+                    Section::Par => Section::Details,
+                    _ => Section::Text,
+                };
+            }
+        }
+        //An unterminated block goes until the end of the comment
+        if let Some(lines) = code {
+            details.push(lines.join("\n"));
+        }
+        doc.flush(&section, &mut current, &mut paragraphs, &mut details);
+
+        //Without `@brief` the first paragraph is the brief description
+        let mut paragraphs = paragraphs.into_iter();
+        doc.explicit_brief = !doc.brief.is_empty();
+        if doc.brief.is_empty() {
+            doc.brief = paragraphs.next().unwrap_or_default();
+        }
+        doc.details = paragraphs.chain(details).collect::<Vec<_>>().join("\n");
+        doc
+    }
+
+    fn flush(
+        &mut self,
+        section: &Section,
+        current: &mut Vec<&str>,
+        paragraphs: &mut Vec<String>,
+        details: &mut Vec<String>,
+    ) {
+        let text = current.join(" ");
+        current.clear();
+        match section {
+            Section::Text if !text.is_empty() => paragraphs.push(text),
+            Section::Text => {}
+            Section::Brief => self.brief = join(&self.brief, &text),
+            Section::Details | Section::Par if !text.is_empty() => details.push(text),
+            Section::Details | Section::Par | Section::Ignored => {}
+            //`@note` without any text says nothing
+            Section::Note if !text.is_empty() => self.notes.push(text),
+            Section::Warning if !text.is_empty() => self.warnings.push(text),
+            Section::Note | Section::Warning => {}
+            Section::Deprecated => {
+                let deprecated = self.deprecated.take().unwrap_or_default();
+                self.deprecated = Some(join(&deprecated, &text));
+            }
+            Section::Param(direction) => self.params.push(ParamDoc::parse(direction, &text)),
+            Section::Returns => self.returns = join(&self.returns, &text),
+            Section::Tag(name) => self.tags.push((name.clone(), text)),
+        }
+    }
+
+    //What a table caption needs: all of an explicit `@brief`, otherwise the first sentence of the brief,
+    //without the final period either way
+    pub fn summary(&self) -> &str {
+        let summary = match self.brief.split_once(". ") {
+            Some((sentence, _)) if !self.explicit_brief => sentence,
+            _ => &self.brief,
+        };
+        summary.strip_suffix('.').unwrap_or(summary)
+    }
+
+    //Everything after the summary, a line per paragraph
+    pub fn description(&self) -> String {
+        let rest = match self.explicit_brief {
+            true => "",
+            false => self
+                .brief
+                .split_once(". ")
+                .map_or("", |(_, rest)| rest.trim()),
+        };
+        [rest, self.details.as_str()]
+            .into_iter()
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    //Returns the text of the first `@name` command
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, text)| text.as_str())
+    }
+}
+
+impl ParamDoc {
+    //`[in] name text` or `name text`, the direction may have been given with the command instead
+    fn parse(direction: &Option<String>, text: &str) -> ParamDoc {
+        let mut direction = direction.clone();
+        let mut text = text.trim();
+        if let Some(rest) = text.strip_prefix('[') {
+            if let Some((inner, rest)) = rest.split_once(']') {
+                direction = Some(inner.replace(' ', ""));
+                text = rest.trim_start();
+            }
+        }
+        let (name, text) = text.split_once(' ').unwrap_or((text, ""));
+        ParamDoc {
+            name: name.to_owned(),
+            direction,
+            text: text.trim().to_owned(),
+        }
+    }
+}
+
+fn join(a: &str, b: &str) -> String {
+    match (a.is_empty(), b.is_empty()) {
+        (true, _) => b.to_owned(),
+        (false, true) => a.to_owned(),
+        (false, false) => format!("{} {}", a, b),
+    }
+}
+
+//`@name` or `\name`, returns the name and the `[...]` attached to it, like in `@param[in]`
+fn command(word: &str) -> Option<(&str, Option<&str>)> {
+    let word = word.strip_prefix('@').or_else(|| word.strip_prefix('\\'))?;
+    //`@code{.c}`
+    let word = word.split_once('{').map_or(word, |(name, _)| name);
+    let (name, attribute) = match word.split_once('[') {
+        Some((name, attribute)) => (name, Some(attribute.strip_suffix(']')?)),
+        None => (word, None),
+    };
+    [
+        &INLINE_COMMANDS[..],
+        &LINE_COMMANDS,
+        &DECLARATION_COMMANDS,
+        &SECTION_COMMANDS,
+        &TAG_COMMANDS,
+    ]
+    .iter()
+    .any(|commands| commands.contains(&name))
+    .then_some((name, attribute))
+}
+
+#[test]
+fn parse_plain_comment_test() {
+    let doc = DocComment::parse("Test struct");
+    assert_eq!(doc.brief, "Test struct");
+    assert_eq!(doc.details, "");
+    assert!(doc.notes.is_empty());
+}
+
+#[test]
+fn parse_commands_test() {
+    let doc = DocComment::parse(
+        "@brief Short\ndescription\n\\details Long description\n@note First note\n\
+         @warning Be careful\n@deprecated\n@since 1.2\n@note Second note",
+    );
+    assert_eq!(doc.brief, "Short description");
+    assert_eq!(doc.details, "Long description");
+    assert_eq!(doc.notes, vec!["First note", "Second note"]);
+    assert_eq!(doc.warnings, vec!["Be careful"]);
+    assert_eq!(doc.deprecated.as_deref(), Some(""));
+    assert_eq!(doc.tag("since"), Some("1.2"));
+}
+
+#[test]
+fn parse_line_commands_test() {
+    let doc =
+        DocComment::parse("@ingroup replies\nReply\n@defgroup protocol Protocol messages\nDetails");
+    assert_eq!(doc.tag("ingroup"), Some("replies"));
+    assert_eq!(doc.tag("defgroup"), Some("protocol Protocol messages"));
+    assert_eq!(doc.brief, "Reply");
+    assert_eq!(doc.details, "Details");
+}
+
+#[test]
+fn summary_test() {
+    let doc = DocComment::parse("Message header. Sent before\nevery message.\n\nThe id is unique.");
+    assert_eq!(doc.brief, "Message header. Sent before every message.");
+    assert_eq!(doc.summary(), "Message header");
+    assert_eq!(
+        doc.description(),
+        "Sent before every message.\nThe id is unique."
+    );
+
+    let doc = DocComment::parse("@brief Message header. Sent first.\n\nThe id is unique.");
+    assert_eq!(doc.summary(), "Message header. Sent first");
+    assert_eq!(doc.description(), "The id is unique.");
+
+    let doc = DocComment::parse("Reads a sensor.");
+    assert_eq!(doc.summary(), "Reads a sensor");
+    assert_eq!(doc.description(), "");
+}
+
+#[test]
+fn parse_unknown_commands_test() {
+    let doc = DocComment::parse("Ends with \\0 terminator and \\n newline\n@note\n@since 1.2");
+    assert_eq!(doc.brief, "Ends with \\0 terminator and \\n newline");
+    assert!(doc.notes.is_empty());
+    assert_eq!(doc.tag("since"), Some("1.2"));
+    let doc = DocComment::parse("Text with \\n inside");
+    assert_eq!(doc.brief, "Text with \\n inside");
+    assert!(doc.tags.is_empty());
+}
+
+#[test]
+fn parse_structural_commands_test() {
+    let doc = DocComment::parse(
+        "@struct msg_s msg.h\nMessage\n@par Example\nSent first\n\
+         @code{.c}\nmsg_s msg = {0};\n  send(&msg);\n@endcode\n@li One\n@li Two",
+    );
+    assert_eq!(doc.brief, "Message");
+    assert_eq!(
+        doc.details,
+        "Example\nSent first\nmsg_s msg = {0};\n  send(&msg);\nOne\nTwo"
+    );
+    assert!(doc.tags.is_empty());
+    let doc = DocComment::parse("\\fn int read(void)\nReads");
+    assert_eq!(doc.brief, "Reads");
+}
+
+#[test]
+fn parse_paragraphs_test() {
+    let doc = DocComment::parse("First paragraph\n\nSecond paragraph\n\n@details Third paragraph");
+    assert_eq!(doc.brief, "First paragraph");
+    assert_eq!(doc.details, "Second paragraph\nThird paragraph");
+}
+
+#[test]
+fn parse_inline_commands_test() {
+    let doc = DocComment::parse("Uses @c value and \\p param, mail me@example.com");
+    assert_eq!(doc.brief, "Uses value and param, mail me@example.com");
+}
+
+#[test]
+fn parse_params_test() {
+    let doc = DocComment::parse(
+        "Sends a message\n@param[in] id Message id\n@param [out] reply Where the reply goes\n\
+         @param timeout Timeout in ms\n@return 0 on success",
+    );
+    assert_eq!(doc.brief, "Sends a message");
+    assert_eq!(doc.params.len(), 3);
+    assert_eq!(doc.params[0].name, "id");
+    assert_eq!(doc.params[0].direction.as_deref(), Some("in"));
+    assert_eq!(doc.params[0].text, "Message id");
+    assert_eq!(doc.params[1].name, "reply");
+    assert_eq!(doc.params[1].direction.as_deref(), Some("out"));
+    assert_eq!(doc.params[2].direction, None);
+    assert_eq!(doc.returns, "0 on success");
+}
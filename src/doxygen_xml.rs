@@ -0,0 +1,734 @@
+//The GPLv3 License (GPLv3)
+//
+//Copyright (c) 2023 Ciubix8513
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    config::Config,
+    doc_comment::DocComment,
+    groups::Group,
+    parser::{
+        DocumentationData, DocumentationItem, DocumentationItemChild, DocumentationType, Location,
+        Parser,
+    },
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+};
+use xml::reader::{EventReader, XmlEvent};
+
+//Anonymous structs in anonymous structs in..., anything deeper is most likely a loop of references
+const MAX_DEPTH: usize = 32;
+
+//Only the parts of the XML the mapping needs, the whole file is small enough to keep in memory
+#[derive(Default, Debug)]
+struct Element {
+    name: String,
+    attributes: HashMap<String, String>,
+    children: Vec<Node>,
+}
+
+#[derive(Debug)]
+enum Node {
+    Element(Element),
+    Text(String),
+}
+
+impl Element {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.get(name).map(String::as_str)
+    }
+
+    fn elements<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter_map(move |node| match node {
+            Node::Element(element) if element.name == name => Some(element),
+            _ => None,
+        })
+    }
+
+    fn element(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find_map(|node| match node {
+            Node::Element(element) if element.name == name => Some(element),
+            _ => None,
+        })
+    }
+
+    //All the text inside, with the whitespace collapsed, `struct <ref>a_s</ref> *` is `struct a_s *`
+    fn text(&self) -> String {
+        let mut text = String::new();
+        self.collect_text(&mut text);
+        text.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    fn collect_text(&self, text: &mut String) {
+        for node in &self.children {
+            match node {
+                Node::Element(element) => element.collect_text(text),
+                Node::Text(t) => text.push_str(t),
+            }
+        }
+    }
+
+    fn child_text(&self, name: &str) -> String {
+        self.element(name).map(Element::text).unwrap_or_default()
+    }
+
+    //The compound or member the first `<ref>` inside points to
+    fn reference(&self) -> Option<&str> {
+        self.children.iter().find_map(|node| match node {
+            Node::Element(element) if element.name == "ref" => element.attribute("refid"),
+            Node::Element(element) => element.reference(),
+            Node::Text(_) => None,
+        })
+    }
+
+    fn into_element(self, name: &str) -> Option<Element> {
+        self.children.into_iter().find_map(|node| match node {
+            Node::Element(element) if element.name == name => Some(element),
+            _ => None,
+        })
+    }
+
+    fn members(&self) -> impl Iterator<Item = &Element> {
+        self.elements("sectiondef")
+            .flat_map(|section| section.elements("memberdef"))
+    }
+}
+
+fn read_xml(path: &Path) -> Result<Element, io::Error> {
+    let file = BufReader::new(File::open(path)?);
+    let mut stack = vec![Element::default()];
+    for event in EventReader::new(file) {
+        let event = event.map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), e),
+            )
+        })?;
+        match event {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => stack.push(Element {
+                name: name.local_name,
+                attributes: attributes
+                    .into_iter()
+                    .map(|a| (a.name.local_name, a.value))
+                    .collect(),
+                children: Vec::new(),
+            }),
+            XmlEvent::EndElement { .. } => {
+                let element = stack.pop().unwrap_or_default();
+                if let Some(parent) = stack.last_mut() {
+                    parent.children.push(Node::Element(element));
+                }
+            }
+            XmlEvent::Characters(text) | XmlEvent::Whitespace(text) | XmlEvent::CData(text) => {
+                if let Some(element) = stack.last_mut() {
+                    element.children.push(Node::Text(text));
+                }
+            }
+            _ => {}
+        }
+    }
+    stack
+        .pop()
+        .and_then(|document| {
+            document.children.into_iter().find_map(|node| match node {
+                Node::Element(element) => Some(element),
+                Node::Text(_) => None,
+            })
+        })
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: no root element", path.display()),
+            )
+        })
+}
+
+//A header as doxygen saw it, the items are built with the same parser code as for the headers we parse
+struct Header<'a> {
+    parser: Parser<'a>,
+    items: Vec<DocumentationItem>,
+    typedefs: HashMap<String, DocumentationItemChild>,
+}
+
+//The output of doxygen with `GENERATE_XML = YES`, `directory` is the one with `index.xml`.
+//Returns the documentation of every header doxygen documented, by the path it reported for them
+pub fn parse_doxygen_xml(
+    directory: &Path,
+    config: &Config,
+) -> Result<Vec<(PathBuf, DocumentationData)>, io::Error> {
+    let index = read_xml(&directory.join("index.xml"))?;
+    //Classes, namespaces, pages and directories don't have anything a C header has
+    let mut compounds = Vec::new();
+    for compound in index.elements("compound") {
+        let (Some(refid), Some(kind)) = (compound.attribute("refid"), compound.attribute("kind"))
+        else {
+            continue;
+        };
+        if matches!(kind, "file" | "struct" | "union" | "group") {
+            let xml = read_xml(&directory.join(format!("{}.xml", refid)))?;
+            if let Some(definition) = xml.into_element("compounddef") {
+                compounds.push((refid.to_owned(), kind.to_owned(), definition));
+            }
+        }
+    }
+    let by_id = compounds
+        .iter()
+        .map(|(refid, _, definition)| (refid.as_str(), definition))
+        .collect::<HashMap<_, _>>();
+    let aggregates = compounds
+        .iter()
+        .filter(|(_, kind, _)| kind == "struct" || kind == "union");
+    let groups = compounds.iter().filter(|(_, kind, _)| kind == "group");
+
+    //Anonymous structs declared inside others are a field of the outer one, not an item of their own
+    let nested = aggregates
+        .clone()
+        .flat_map(|(_, _, definition)| definition.elements("innerclass"))
+        .filter_map(|inner| inner.attribute("refid"))
+        .collect::<HashSet<_>>();
+    //Member or compound id -> the name of its group
+    let mut membership = HashMap::new();
+    let mut parents = HashMap::new();
+    for (_, _, group) in groups.clone() {
+        let name = group.child_text("compoundname");
+        let classes = group
+            .elements("innerclass")
+            .filter_map(|inner| inner.attribute("refid"));
+        let members = group.members().filter_map(|member| member.attribute("id"));
+        for id in classes.chain(members) {
+            membership.entry(id.to_owned()).or_insert(name.clone());
+        }
+        for inner in group
+            .elements("innergroup")
+            .filter_map(|g| g.attribute("refid"))
+        {
+            parents.insert(inner.to_owned(), name.clone());
+        }
+    }
+    //`typedef struct reading_s reading_t;` names the struct
+    let mut aliases = HashMap::new();
+    for (_, _, compound) in &compounds {
+        for member in compound
+            .members()
+            .filter(|m| m.attribute("kind") == Some("typedef"))
+        {
+            let r#type = member.element("type");
+            if let Some(refid) = r#type.and_then(Element::reference) {
+                if !r#type.map(Element::text).unwrap_or_default().contains('*') {
+                    aliases
+                        .entry(refid.to_owned())
+                        .or_insert_with(|| (member.child_text("name"), comment(member)));
+                }
+            }
+        }
+    }
+
+    let mut headers = Vec::new();
+
+    //Members of a group are listed both by the group and by the file
+    let mut seen = HashSet::new();
+    let members = compounds
+        .iter()
+        .filter(|(_, kind, _)| kind == "file" || kind == "group")
+        .flat_map(|(_, _, compound)| compound.members())
+        .filter(|member| seen.insert(member.attribute("id").unwrap_or_default().to_owned()))
+        .collect::<Vec<_>>();
+    //The defines first, enumerators may refer to them
+    let kind = |member: &Element| member.attribute("kind").unwrap_or_default().to_owned();
+    for member in members.iter().filter(|m| kind(m) == "define") {
+        let location = location(member);
+        let h = header(&mut headers, &location, config);
+        let name = member.child_text("name");
+        let value = member.child_text("initializer");
+        let parameters = member
+            .elements("param")
+            .map(|p| p.child_text("defname"))
+            .collect::<Vec<_>>();
+        let name = match parameters.is_empty() && member.element("param").is_none() {
+            true => {
+                h.parser.define_from(&name, &value);
+                name
+            }
+            false => format!("{}({})", name, parameters.join(", ")),
+        };
+        let comment = comment(member);
+        if comment.is_empty() {
+            continue;
+        }
+        let doc = DocComment::parse(&comment);
+        h.items.push(DocumentationItem {
+            r#type: DocumentationType::Define,
+            note: doc.brief.clone(),
+            doc,
+            name,
+            value,
+            location,
+            group: group_of(member, &membership),
+            ..Default::default()
+        });
+    }
+    for member in members.iter().filter(|m| kind(m) != "define") {
+        let location = location(member);
+        let h = header(&mut headers, &location, config);
+        let name = member.child_text("name");
+        let r#type = member.child_text("type");
+        let args = member.child_text("argsstring");
+        let comment = comment(member);
+        let item = match kind(member).as_str() {
+            //`typedef struct reading_s reading_t;` is the name of the struct, not a typedef of its own
+            "typedef"
+                if member
+                    .element("type")
+                    .and_then(Element::reference)
+                    .is_some_and(|refid| aliases.contains_key(refid)) =>
+            {
+                continue
+            }
+            "typedef" => {
+                let child = h.parser.member_from(
+                    &format!("{} {}{}", r#type, name, args),
+                    "",
+                    &DocumentationType::Struct,
+                );
+                if !child.name.is_empty() && !child.base_type.is_empty() {
+                    h.typedefs.insert(child.name.clone(), child);
+                }
+                continue;
+            }
+            "function" if !comment.is_empty() => {
+                match h
+                    .parser
+                    .function_from(&format!("{} {}{}", r#type, name, args), &comment)
+                {
+                    Some(item) => item,
+                    None => continue,
+                }
+            }
+            "enum" => {
+                //Named by the typedef like the structs, anonymous enums are `@0`
+                let alias = aliases.get(member.attribute("id").unwrap_or_default());
+                let tag = Some(name).filter(|name| !name.starts_with('@'));
+                let Some(name) = alias.map(|(alias, _)| alias).or(tag.as_ref()).cloned() else {
+                    continue;
+                };
+                let comment = match comment.is_empty() {
+                    true => alias
+                        .map(|(_, comment)| comment.clone())
+                        .unwrap_or_default(),
+                    false => comment,
+                };
+                if comment.is_empty() {
+                    continue;
+                }
+                let doc = DocComment::parse(&comment);
+                let mut item = DocumentationItem {
+                    r#type: DocumentationType::Enum,
+                    note: doc.summary().to_owned(),
+                    doc,
+                    name,
+                    tag,
+                    alias: alias.map(|(alias, _)| alias.clone()),
+                    ..Default::default()
+                };
+                h.parser.enum_from();
+                for value in member.elements("enumvalue") {
+                    let declaration = format!(
+                        "{} {}",
+                        value.child_text("name"),
+                        value.child_text("initializer")
+                    );
+                    let comment = self::comment(value);
+                    let mut child =
+                        h.parser
+                            .member_from(&declaration, &comment, &DocumentationType::Enum);
+                    child.location = location.clone();
+                    match comment.is_empty() {
+                        true => item.undocumented.push((item.children.len(), child)),
+                        false => item.children.push(child),
+                    }
+                }
+                item
+            }
+            _ => continue,
+        };
+        h.items.push(DocumentationItem {
+            location,
+            group: group_of(member, &membership),
+            ..item
+        });
+    }
+
+    for (refid, kind, compound) in aggregates {
+        if nested.contains(refid.as_str()) {
+            continue;
+        }
+        let alias = aliases.get(refid.as_str());
+        let mut comment = comment(compound);
+        if comment.is_empty() {
+            //Documented through the typedef
+            comment = alias
+                .map(|(_, comment)| comment.clone())
+                .unwrap_or_default();
+        }
+        if comment.is_empty() {
+            continue;
+        }
+        let location = location(compound);
+        let h = header(&mut headers, &location, config);
+        let compounds = Compounds {
+            by_id: &by_id,
+            nested: &nested,
+        };
+        let mut item = aggregate(compound, kind, &comment, &compounds, &mut h.parser, 0);
+        let tag = compound.child_text("compoundname");
+        if let Some((alias, _)) = alias {
+            item.name = alias.clone();
+            item.alias = Some(alias.clone());
+        }
+        item.tag = Some(tag);
+        item.group = membership.get(refid.as_str()).cloned();
+        h.items.push(item);
+    }
+
+    let groups = groups
+        .map(|(refid, _, group)| Group {
+            name: group.child_text("compoundname"),
+            title: match group.child_text("title") {
+                title if title.is_empty() => group.child_text("compoundname"),
+                title => title,
+            },
+            doc: DocComment::parse(&comment(group)),
+            parent: parents.get(refid.as_str()).cloned(),
+            location: Location::default(),
+        })
+        .collect::<Vec<_>>();
+    Ok(headers
+        .into_iter()
+        .map(|(file, h)| {
+            let mut data = h.parser.finish();
+            data.items = h.items;
+            //In the order they are declared, like a parsed header
+            data.items.sort_by_key(|i| i.location.start_line);
+            data.typedefs.extend(h.typedefs);
+            data.groups = groups.clone();
+            (file, data)
+        })
+        .collect())
+}
+
+//The header an item is declared in, the first item of a header adds it
+fn header<'a, 'b>(
+    headers: &'b mut Vec<(PathBuf, Header<'a>)>,
+    location: &Location,
+    config: &'a Config,
+) -> &'b mut Header<'a> {
+    let i = match headers.iter().position(|(file, _)| file == &location.file) {
+        Some(i) => i,
+        None => {
+            headers.push((
+                location.file.clone(),
+                Header {
+                    parser: Parser::new(config, "", &[]),
+                    items: Vec::new(),
+                    typedefs: HashMap::new(),
+                },
+            ));
+            headers.len() - 1
+        }
+    };
+    &mut headers[i].1
+}
+
+//The struct and union compounds the fields can refer to
+struct Compounds<'a> {
+    by_id: &'a HashMap<&'a str, &'a Element>,
+    //Declared inside another struct or union
+    nested: &'a HashSet<&'a str>,
+}
+
+//A struct or union compound, the nested ones are built from the compounds their fields refer to
+fn aggregate(
+    compound: &Element,
+    kind: &str,
+    comment: &str,
+    compounds: &Compounds,
+    parser: &mut Parser,
+    depth: usize,
+) -> DocumentationItem {
+    let doc = DocComment::parse(comment);
+    let r#type = match kind {
+        "union" => DocumentationType::Union,
+        _ => DocumentationType::Struct,
+    };
+    let mut item = DocumentationItem {
+        r#type: r#type.clone(),
+        note: doc.summary().to_owned(),
+        doc,
+        name: compound.child_text("compoundname"),
+        location: location(compound),
+        ..Default::default()
+    };
+    for member in compound
+        .members()
+        .filter(|m| m.attribute("kind") == Some("variable"))
+    {
+        let name = member.child_text("name");
+        let args = member.child_text("argsstring");
+        let comment = self::comment(member);
+        let inner = member
+            .element("type")
+            .and_then(Element::reference)
+            .filter(|refid| compounds.nested.contains(refid))
+            .and_then(|refid| compounds.by_id.get(refid));
+        let mut declaration = match inner {
+            //The type is only `struct` or `union`, the name doxygen made up for it isn't C
+            Some(inner) => format!(
+                "{} {}{}",
+                inner.attribute("kind").unwrap_or_default(),
+                name,
+                args
+            ),
+            None => format!("{} {}{}", member.child_text("type"), name, args),
+        };
+        if let Some(width) = member.element("bitfield") {
+            declaration += &format!(" : {}", width.text());
+        }
+        declaration += ";";
+        let mut child = parser.member_from(&declaration, &comment, &r#type);
+        child.location = location(member);
+        if let Some(inner) = inner {
+            //Written like the header parser writes a nested declaration
+            let kind = inner.attribute("kind").unwrap_or_default();
+            child.datatype = format!("{} {{ ... }} {};", kind, child.name);
+        }
+        if let Some(inner) = inner.filter(|_| depth < MAX_DEPTH) {
+            let kind = inner.attribute("kind").unwrap_or_default();
+            child.nested = Some(Box::new(aggregate(
+                inner,
+                kind,
+                "",
+                compounds,
+                parser,
+                depth + 1,
+            )));
+        }
+        match comment.is_empty() {
+            true => item.undocumented.push((item.children.len(), child)),
+            false => item.children.push(child),
+        }
+    }
+    item
+}
+
+fn group_of(member: &Element, membership: &HashMap<String, String>) -> Option<String> {
+    membership
+        .get(member.attribute("id").unwrap_or_default())
+        .cloned()
+}
+
+//`<location file="include/a.h" line="12" bodystart="12" bodyend="15"/>`
+fn location(element: &Element) -> Location {
+    let Some(location) = element.element("location") else {
+        return Location::default();
+    };
+    let line = |name| {
+        location
+            .attribute(name)
+            .and_then(|l| l.parse::<usize>().ok())
+            .filter(|l| *l > 0)
+    };
+    let start_line = line("bodystart").or(line("line")).unwrap_or_default();
+    Location {
+        file: PathBuf::from(location.attribute("file").unwrap_or_default()),
+        start_line,
+        end_line: line("bodyend")
+            .filter(|end| *end >= start_line)
+            .unwrap_or(start_line),
+        ..Default::default()
+    }
+}
+
+//The brief and the detailed description turned back into the comment doxygen read,
+//so that the annotations and the commands are parsed the same way as in a header
+fn comment(element: &Element) -> String {
+    let description = |name| {
+        element
+            .element(name)
+            .map(description)
+            .filter(|text| !text.is_empty())
+    };
+    //Doxygen already told the brief apart, it's whole even if it has more than one sentence
+    let brief = description("briefdescription").map(|brief| format!("@brief {}", brief));
+    brief
+        .into_iter()
+        .chain(description("detaileddescription"))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn description(element: &Element) -> String {
+    element
+        .elements("para")
+        .map(|para| paragraph(para).trim().to_owned())
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn paragraph(element: &Element) -> String {
+    let mut text = String::new();
+    for node in &element.children {
+        let element = match node {
+            Node::Text(t) => {
+                text += &t.replace('\n', " ");
+                continue;
+            }
+            Node::Element(element) => element,
+        };
+        match element.name.as_str() {
+            "simplesect" => {
+                let command = match element.attribute("kind").unwrap_or_default() {
+                    "return" => "return",
+                    "warning" | "attention" => "warning",
+                    "note" | "remark" => "note",
+                    kind => kind,
+                };
+                text += &format!("\n\n@{} {}\n\n", command, description(element));
+            }
+            "parameterlist" if element.attribute("kind") == Some("param") => {
+                for item in element.elements("parameteritem") {
+                    let names = item
+                        .elements("parameternamelist")
+                        .flat_map(|n| n.elements("parametername"));
+                    let description = item
+                        .element("parameterdescription")
+                        .map(description)
+                        .unwrap_or_default();
+                    for name in names {
+                        let direction = name
+                            .attribute("direction")
+                            .map(|d| format!("[{}]", d))
+                            .unwrap_or_default();
+                        text += &format!(
+                            "\n\n@param{} {} {}\n\n",
+                            direction,
+                            name.text(),
+                            description
+                        );
+                    }
+                }
+            }
+            //`@deprecated` and `@todo` are cross references
+            "xrefsect" => {
+                let title = element.child_text("xreftitle").to_lowercase();
+                let description = element
+                    .element("xrefdescription")
+                    .map(description)
+                    .unwrap_or_default();
+                text += &format!("\n\n@{} {}\n\n", title, description);
+            }
+            "linebreak" | "sp" => text += " ",
+            //`<ref>`, `<computeroutput>`, `<bold>` and the rest of the inline markup
+            _ => text += &paragraph(element),
+        }
+    }
+    text
+}
+
+#[test]
+fn parse_doxygen_xml_test() {
+    let headers =
+        parse_doxygen_xml(Path::new("test_data/doxygen_xml"), &Config::default()).unwrap();
+    assert_eq!(headers.len(), 1);
+    let (file, data) = &headers[0];
+    assert_eq!(file, Path::new("include/sensor.h"));
+    let names = data
+        .items
+        .iter()
+        .map(|i| i.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            "SENSOR_API_VERSION",
+            "SENSOR_COUNT",
+            "sensor_kind",
+            "reading_t",
+            "read_sensor",
+            "mode_t",
+            "calibration_t"
+        ]
+    );
+
+    let kind = &data.items[2];
+    assert_eq!(kind.r#type, DocumentationType::Enum);
+    let values = kind.children.iter().map(|c| c.value).collect::<Vec<_>>();
+    assert_eq!(values, [Some(0), Some(5), Some(6)]);
+    assert_eq!(kind.group.as_deref(), Some("sensor"));
+
+    let reading = &data.items[3];
+    assert_eq!(reading.note, "Sensor reading");
+    assert_eq!(reading.tag.as_deref(), Some("reading_s"));
+    assert_eq!(reading.location.start_line, 21);
+    assert_eq!(reading.location.end_line, 29);
+    //The annotations are parsed like in a header
+    assert_eq!(reading.children[0].code, "ID");
+    assert_eq!(reading.children[1].array_dims, ["SENSOR_COUNT"]);
+    assert_eq!(reading.children[1].note, "Values");
+    assert_eq!(reading.children[1].annotation.fields[0].key, "unit");
+    assert_eq!(reading.undocumented[0].1.bit_width.as_deref(), Some("3"));
+    let pos = reading.children[2].nested.as_ref().unwrap();
+    assert_eq!(pos.children.len(), 2);
+    assert_eq!(reading.children[2].declaration(), "struct { ... } pos");
+
+    let read = &data.items[4];
+    assert_eq!(read.note, "Reads a sensor");
+    assert_eq!(read.parameters[1].direction, "out");
+    assert_eq!(read.parameters[1].note, "Where the reading goes");
+    assert_eq!(read.doc.returns, "0 on success");
+    assert_eq!(read.doc.notes, ["Not reentrant"]);
+
+    //`typedef enum { ... } mode_t;` is `@0`, documented through the typedef
+    let mode = &data.items[5];
+    assert_eq!(mode.r#type, DocumentationType::Enum);
+    assert_eq!(mode.note, "Sampling mode");
+    assert_eq!(
+        (mode.tag.as_deref(), mode.alias.as_deref()),
+        (None, Some("mode_t"))
+    );
+    assert_eq!(mode.children.len(), 2);
+    assert!(!data.typedefs.contains_key("mode_t"));
+    //Doxygen names `typedef struct { ... } calibration_t;` after the typedef
+    let calibration = &data.items[6];
+    assert_eq!(calibration.note, "Calibration");
+    assert_eq!(calibration.alias.as_deref(), Some("calibration_t"));
+    let fields = calibration
+        .children
+        .iter()
+        .map(|c| c.declaration())
+        .collect::<Vec<_>>();
+    assert_eq!(fields, ["int16_t offset", "uint16_t gain"]);
+    assert!(!data.typedefs.contains_key("calibration_t"));
+
+    assert_eq!(data.groups[0].title, "Sensors");
+    assert_eq!(data.typedefs["word_t"].base_type, "uint16_t");
+    assert_eq!(data.macros["SENSOR_COUNT"], "4");
+}
@@ -0,0 +1,414 @@
+//The GPLv3 License (GPLv3)
+//
+//Copyright (c) 2023 Ciubix8513
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::lexer::{tokenize, Token, TokenKind};
+use std::collections::HashMap;
+
+//Macros referencing macros referencing macros..., anything deeper is most likely a cycle
+const MAX_MACRO_DEPTH: usize = 32;
+
+//The names an expression can refer to: enumerators and object-like macros
+#[derive(Default, Debug, Clone)]
+pub struct Constants {
+    pub values: HashMap<String, i64>,
+    //Macros are only evaluated when they are used, just like the preprocessor would
+    pub macros: HashMap<String, String>,
+    //The value of names that are neither, `#if` treats them as 0
+    pub undefined: Option<i64>,
+}
+
+impl Constants {
+    pub fn value(&self, name: &str) -> Option<i64> {
+        self.value_at_depth(name, 0)
+    }
+
+    fn value_at_depth(&self, name: &str, depth: usize) -> Option<i64> {
+        if let Some(value) = self.values.get(name) {
+            return Some(*value);
+        }
+        if depth >= MAX_MACRO_DEPTH {
+            return None;
+        }
+        let tokens = match self.macros.get(name) {
+            Some(value) => tokenize(value),
+            None => return self.undefined,
+        };
+        evaluate(&tokens, &|name| self.value_at_depth(name, depth + 1))
+    }
+
+    pub fn evaluate_str(&self, expression: &str) -> Option<i64> {
+        evaluate(&tokenize(expression), &|name| self.value(name))
+    }
+}
+
+//Evaluates a C constant expression, None if it isn't one or refers to something unknown
+pub fn evaluate(tokens: &[Token], resolve: &dyn Fn(&str) -> Option<i64>) -> Option<i64> {
+    let tokens = tokens
+        .iter()
+        .filter(|t| !t.is_comment())
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut evaluator = Evaluator {
+        tokens: &tokens,
+        pos: 0,
+        resolve,
+    };
+    let value = evaluator.expression(0)?;
+    //Trailing garbage means we didn't understand the expression
+    (evaluator.pos == tokens.len()).then_some(value)
+}
+
+struct Evaluator<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    resolve: &'a dyn Fn(&str) -> Option<i64>,
+}
+
+fn precedence(op: &str) -> Option<u8> {
+    Some(match op {
+        "||" => 2,
+        "&&" => 3,
+        "|" => 4,
+        "^" => 5,
+        "&" => 6,
+        "==" | "!=" => 7,
+        "<" | "<=" | ">" | ">=" => 8,
+        "<<" | ">>" => 9,
+        "+" | "-" => 10,
+        "*" | "/" | "%" => 11,
+        _ => return None,
+    })
+}
+
+impl<'a> Evaluator<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn expect(&mut self, punct: &str) -> Option<()> {
+        self.peek().filter(|t| t.is_punct(punct))?;
+        self.pos += 1;
+        Some(())
+    }
+
+    //Precedence climbing, the ternary operator binds the weakest
+    fn expression(&mut self, min_precedence: u8) -> Option<i64> {
+        let mut lhs = self.unary()?;
+        while let Some(token) = self.peek().filter(|t| t.kind == TokenKind::Punct) {
+            if token.text == "?" && min_precedence <= 1 {
+                self.pos += 1;
+                let a = self.expression(0)?;
+                self.expect(":")?;
+                let b = self.expression(1)?;
+                lhs = if lhs != 0 { a } else { b };
+                continue;
+            }
+            let precedence = match precedence(&token.text) {
+                Some(precedence) if precedence >= min_precedence => precedence,
+                _ => break,
+            };
+            self.pos += 1;
+            let rhs = self.expression(precedence + 1)?;
+            lhs = binary(&token.text, lhs, rhs)?;
+        }
+        Some(lhs)
+    }
+
+    fn unary(&mut self) -> Option<i64> {
+        let token = self.peek()?;
+        self.pos += 1;
+        match token.kind {
+            TokenKind::Number => parse_integer(&token.text),
+            TokenKind::Char => parse_char(&token.text),
+            TokenKind::Identifier => (self.resolve)(&token.text),
+            TokenKind::Punct => match token.text.as_str() {
+                "-" => self.unary().map(i64::wrapping_neg),
+                "+" => self.unary(),
+                "~" => self.unary().map(|v| !v),
+                "!" => self.unary().map(|v| (v == 0) as i64),
+                "(" if self.is_cast() => {
+                    let start = self.pos;
+                    while !self.peek()?.is_punct(")") {
+                        self.pos += 1;
+                    }
+                    let r#type = &self.tokens[start..self.pos];
+                    self.pos += 1;
+                    cast(r#type, self.unary()?)
+                }
+                "(" => {
+                    let value = self.expression(0)?;
+                    self.expect(")")?;
+                    Some(value)
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    //`(uint8_t)x`, the opening parenthesis is already consumed
+    fn is_cast(&self) -> bool {
+        let close = match self.tokens[self.pos..].iter().position(|t| t.is_punct(")")) {
+            Some(close) => self.pos + close,
+            None => return false,
+        };
+        let inner = &self.tokens[self.pos..close];
+        let next = self.tokens.get(close + 1);
+        //Nothing that can follow a parenthesised expression
+        let operand = next.is_some_and(|t| {
+            matches!(
+                t.kind,
+                TokenKind::Number | TokenKind::Char | TokenKind::Identifier
+            ) || ["(", "~", "!"].iter().any(|p| t.is_punct(p))
+        });
+        //`(x) - 1` is a subtraction unless x is a type we know about
+        let signed_operand = next.is_some_and(|t| t.is_punct("-") || t.is_punct("+"))
+            && inner.iter().any(|t| is_type_name(&t.text));
+        !inner.is_empty()
+            && (operand || signed_operand)
+            && inner
+                .iter()
+                .all(|t| t.kind == TokenKind::Identifier || t.is_punct("*"))
+            && (self.resolve)(&inner[0].text).is_none()
+    }
+}
+
+//The builtin and <stdint.h>/<stddef.h> types
+fn is_type_name(name: &str) -> bool {
+    let builtin = matches!(
+        name,
+        "void" | "char" | "short" | "int" | "long" | "float" | "double" | "signed" | "unsigned"
+    );
+    let stddef = matches!(name, "_Bool" | "bool" | "size_t" | "ptrdiff_t")
+        || matches!(name, "intptr_t" | "uintptr_t" | "intmax_t" | "uintmax_t");
+    if builtin || stddef {
+        return true;
+    }
+    let digits = name
+        .strip_prefix("uint")
+        .or_else(|| name.strip_prefix("int"))
+        .and_then(|n| n.strip_suffix("_t"));
+    matches!(digits, Some("8" | "16" | "32" | "64"))
+}
+
+//The value converted to the type, None if its width depends on the target and the value doesn't fit all of them,
+//or if the type isn't an integer one we know
+fn cast(r#type: &[Token], value: i64) -> Option<i64> {
+    let words = r#type
+        .iter()
+        .map(|t| t.text.as_str())
+        .filter(|w| !matches!(*w, "const" | "volatile"))
+        .collect::<Vec<_>>();
+    let signed = !words.contains(&"unsigned");
+    //(bits, signed) for every target the type could have
+    let widths: &[(u32, bool)] = match words.as_slice() {
+        ["_Bool"] | ["bool"] => return Some((value != 0) as i64),
+        ["intmax_t"] => &[(64, true)],
+        ["uintmax_t"] => &[(64, false)],
+        ["intptr_t"] | ["ptrdiff_t"] => &[(16, true), (32, true), (64, true)],
+        ["uintptr_t"] | ["size_t"] => &[(16, false), (32, false), (64, false)],
+        [name] if is_type_name(name) && name.ends_with("_t") => {
+            let bits = name.trim_start_matches('u').trim_start_matches("int");
+            let bits = bits.strip_suffix("_t")?.parse().ok()?;
+            return Some(truncate(value, bits, !name.starts_with('u')));
+        }
+        words
+            if !words.is_empty()
+                && words.iter().all(|w| {
+                    matches!(
+                        *w,
+                        "char" | "short" | "int" | "long" | "signed" | "unsigned"
+                    )
+                }) =>
+        {
+            let longs = words.iter().filter(|w| **w == "long").count();
+            if words.contains(&"char") {
+                match (words.contains(&"signed"), words.contains(&"unsigned")) {
+                    (true, _) => &[(8, true)],
+                    (_, true) => &[(8, false)],
+                    //Plain `char` is unsigned on ARM
+                    _ => &[(8, true), (8, false)],
+                }
+            } else if words.contains(&"short") {
+                if signed {
+                    &[(16, true)]
+                } else {
+                    &[(16, false)]
+                }
+            } else {
+                match (longs, signed) {
+                    (0, true) => &[(16, true), (32, true)],
+                    (0, false) => &[(16, false), (32, false)],
+                    (1, true) => &[(32, true), (64, true)],
+                    (1, false) => &[(32, false), (64, false)],
+                    (_, true) => &[(64, true)],
+                    (_, false) => &[(64, false)],
+                }
+            }
+        }
+        _ => return None,
+    };
+    let result = truncate(value, widths[0].0, widths[0].1);
+    widths
+        .iter()
+        .all(|(bits, signed)| truncate(value, *bits, *signed) == result)
+        .then_some(result)
+}
+
+//Keeps the low bits of the value, sign extended if the type is signed
+fn truncate(value: i64, bits: u32, signed: bool) -> i64 {
+    if bits >= 64 {
+        return value;
+    }
+    let value = value & ((1 << bits) - 1);
+    match signed && value >> (bits - 1) != 0 {
+        true => value - (1 << bits),
+        false => value,
+    }
+}
+
+fn binary(op: &str, a: i64, b: i64) -> Option<i64> {
+    Some(match op {
+        "||" => (a != 0 || b != 0) as i64,
+        "&&" => (a != 0 && b != 0) as i64,
+        "|" => a | b,
+        "^" => a ^ b,
+        "&" => a & b,
+        "==" => (a == b) as i64,
+        "!=" => (a != b) as i64,
+        "<" => (a < b) as i64,
+        "<=" => (a <= b) as i64,
+        ">" => (a > b) as i64,
+        ">=" => (a >= b) as i64,
+        "<<" => a.checked_shl(u32::try_from(b).ok()?)?,
+        ">>" => a.checked_shr(u32::try_from(b).ok()?)?,
+        "+" => a.wrapping_add(b),
+        "-" => a.wrapping_sub(b),
+        "*" => a.wrapping_mul(b),
+        "/" => a.checked_div(b)?,
+        "%" => a.checked_rem(b)?,
+        _ => return None,
+    })
+}
+
+//`42`, `0x2A`, `052`, `0b101010`, with any `u`/`l` suffixes
+pub fn parse_integer(literal: &str) -> Option<i64> {
+    let literal = literal.trim_end_matches(['u', 'U', 'l', 'L']);
+    let lower = literal.to_ascii_lowercase();
+    let (digits, radix) = if let Some(hex) = lower.strip_prefix("0x") {
+        (hex, 16)
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        (binary, 2)
+    } else if lower.len() > 1 && lower.starts_with('0') {
+        (&lower[1..], 8)
+    } else {
+        (lower.as_str(), 10)
+    };
+    u64::from_str_radix(digits, radix).ok().map(|v| v as i64)
+}
+
+//`'a'`, `'\n'`, `'\x41'`, `'\101'`
+fn parse_char(literal: &str) -> Option<i64> {
+    let inner = literal
+        .trim_start_matches(['L', 'u', 'U'])
+        .strip_prefix('\'')?
+        .strip_suffix('\'')?;
+    let mut chars = inner.chars();
+    let value = match chars.next()? {
+        '\\' => {
+            let escape = chars.as_str();
+            let value = match escape.chars().next()? {
+                'n' => '\n' as i64,
+                't' => '\t' as i64,
+                'r' => '\r' as i64,
+                'a' => 7,
+                'b' => 8,
+                'f' => 12,
+                'v' => 11,
+                'x' => return i64::from_str_radix(&escape[1..], 16).ok(),
+                '0'..='7' => return i64::from_str_radix(escape, 8).ok(),
+                c => c as i64,
+            };
+            chars.next();
+            value
+        }
+        c => c as i64,
+    };
+    //Multi character constants aren't worth supporting
+    chars.as_str().is_empty().then_some(value)
+}
+
+#[test]
+fn evaluate_literals_test() {
+    let constants = Constants::default();
+    assert_eq!(constants.evaluate_str("42"), Some(42));
+    assert_eq!(constants.evaluate_str("0x2Au"), Some(42));
+    assert_eq!(constants.evaluate_str("052"), Some(42));
+    assert_eq!(constants.evaluate_str("0b101010UL"), Some(42));
+    assert_eq!(constants.evaluate_str("'*'"), Some(42));
+    assert_eq!(constants.evaluate_str("'\\n'"), Some(10));
+    assert_eq!(constants.evaluate_str("'\\x2a'"), Some(42));
+    assert_eq!(constants.evaluate_str("'\\052'"), Some(42));
+    assert_eq!(constants.evaluate_str("1.5"), None);
+}
+
+#[test]
+fn evaluate_operators_test() {
+    let constants = Constants::default();
+    assert_eq!(constants.evaluate_str("1 << 3 | 1"), Some(9));
+    assert_eq!(constants.evaluate_str("(1 + 2) * 3 - 10 / 2"), Some(4));
+    assert_eq!(constants.evaluate_str("-1 & 0xFF"), Some(255));
+    assert_eq!(constants.evaluate_str("~0 >> 60"), Some(-1));
+    assert_eq!(constants.evaluate_str("1 ? 2 : 3"), Some(2));
+    assert_eq!(constants.evaluate_str("!0 + (2 > 1) + (1 == 2)"), Some(2));
+    assert_eq!(constants.evaluate_str("(uint8_t)0x1FF"), Some(0xFF));
+    assert_eq!(constants.evaluate_str("(int8_t)0xFF"), Some(-1));
+    assert_eq!(constants.evaluate_str("(uint16_t)-1"), Some(0xFFFF));
+    assert_eq!(constants.evaluate_str("(int32_t)-1"), Some(-1));
+    assert_eq!(constants.evaluate_str("(unsigned char)0x1FF"), Some(0xFF));
+    assert_eq!(constants.evaluate_str("(long long)0x1FF"), Some(0x1FF));
+    assert_eq!(constants.evaluate_str("(bool)4"), Some(1));
+    //The width of `int` depends on the target, 0x12345 only fits some of them
+    assert_eq!(constants.evaluate_str("(int)100"), Some(100));
+    assert_eq!(constants.evaluate_str("(int)0x12345"), None);
+    assert_eq!(constants.evaluate_str("(char)200"), None);
+    assert_eq!(constants.evaluate_str("(word_t)1"), None);
+    assert_eq!(constants.evaluate_str("(void *)0"), None);
+    assert_eq!(constants.evaluate_str("(UNKNOWN) - 1"), None);
+    assert_eq!(constants.evaluate_str("1 / 0"), None);
+    assert_eq!(constants.evaluate_str("1 +"), None);
+}
+
+#[test]
+fn evaluate_symbols_test() {
+    let mut constants = Constants::default();
+    constants.values.insert("A".to_owned(), 4);
+    constants
+        .macros
+        .insert("MASK".to_owned(), "(1u << SHIFT)".to_owned());
+    constants
+        .macros
+        .insert("SHIFT".to_owned(), "A + 1".to_owned());
+    constants
+        .macros
+        .insert("LOOP".to_owned(), "LOOP + 1".to_owned());
+    assert_eq!(constants.evaluate_str("MASK | A"), Some(36));
+    assert_eq!(constants.evaluate_str("LOOP"), None);
+    assert_eq!(constants.evaluate_str("UNKNOWN"), None);
+    constants.undefined = Some(0);
+    assert_eq!(constants.evaluate_str("UNKNOWN + MASK"), Some(32));
+}
@@ -15,9 +15,14 @@
 //You should have received a copy of the GNU General Public License
 //along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use crate::parser::DocumentationData;
+use crate::{
+    annotation::{Column, ScaleKind, Sign},
+    config::ExportConfig,
+    groups::{sections, Group, Section},
+    parser::{DocumentationData, DocumentationItem, DocumentationItemChild, DocumentationType},
+};
 use docx_rs::*;
-use std::{fs::File, path::PathBuf};
+use std::{collections::HashMap, fs::File, path::PathBuf};
 
 //Some macros to make my life easier
 #[macro_export]
@@ -41,53 +46,543 @@ macro_rules! cell {
     };
 }
 
-pub fn export_doc(data: DocumentationData, file: PathBuf) -> Result<(), std::io::Error> {
+//What a single table of the document is made of
+enum TableSource<'a> {
+    Item(&'a DocumentationItem),
+    //Defines sharing a name prefix, the prefix is empty for the ones that don't share it with anything
+    Defines(String, Vec<&'a DocumentationItem>),
+}
+
+//What the document is made of, in order
+enum Block<'a> {
+    //A group with its number, `1.2`, the tables of the group follow it
+    Heading(String, &'a Group),
+    Table(TableSource<'a>),
+}
+
+pub fn export_doc(
+    data: DocumentationData,
+    file: PathBuf,
+    options: &ExportConfig,
+) -> Result<(), std::io::Error> {
     //Open file first, just so that we don't have to do the pdf generation if the path is incorrect
     let file = File::create(file)?;
     //29700;21000 = 52.39;37.04
     //page_orient isn't working rn, so I had to do it manually
     let mut doc = Docx::new().page_size(16837, 11905);
-    let mut table_count = 1;
-    //While I can make a system for defining the tables, it'd be quite a pain for this project
-    let header = vec![TableRow::new(vec![
-        cell!("Название элемента структуры"),
-        cell!("Код параметра"),
-        cell!("Наименование параметра (сигнала)"),
-        cell!("ЦСР (ЦМР)"),
-        cell!("Знак"),
-        cell!("Размещение в разряде"),
-        cell!("Примечание"),
-    ])];
 
-    for item in data.items {
+    let root = sections(&data);
+    let mut blocks = Vec::new();
+    section_blocks(&root, "", &mut blocks);
+    //Fields of documented types refer to the table of the type
+    let mut tables = HashMap::new();
+    let sources = blocks.iter().filter_map(|block| match block {
+        Block::Table(source) => Some(source),
+        Block::Heading(..) => None,
+    });
+    for (i, source) in sources.enumerate() {
+        if let TableSource::Item(item) = source {
+            tables.entry(item.name.clone()).or_insert(i + 1);
+        }
+    }
+
+    let mut table_count = 0;
+    for block in blocks {
+        let source = match block {
+            Block::Heading(number, group) => {
+                doc = heading(doc, &number, group);
+                continue;
+            }
+            Block::Table(source) => source,
+        };
+        let (mut caption, table) = match source {
+            TableSource::Item(item) if item.r#type == DocumentationType::Function => {
+                (item_caption(item), function_table(item))
+            }
+            TableSource::Item(item) => (item_caption(item), item_table(item, &tables, options)),
+            TableSource::Defines(ref prefix, ref defines) => (
+                match prefix.is_empty() {
+                    true => "Константы".to_owned(),
+                    false => format!("Константы ({}*)", prefix),
+                },
+                defines_table(defines, options),
+            ),
+        };
+        //The defines have a line each, only the file is common to the whole table
+        if options.locations {
+            caption += &match &source {
+                TableSource::Item(item) => format!(", {}", item.location),
+                TableSource::Defines(_, defines) => match defines[0].location.file.file_name() {
+                    Some(name) => format!(", {}", name.to_string_lossy()),
+                    None => String::new(),
+                },
+            };
+        }
+        //Whatever the caption leaves out of the comment goes before it
+        if let TableSource::Item(item) = &source {
+            for paragraph in item.doc.description().lines() {
+                doc = doc.add_paragraph(paragraph!(paragraph, AlignmentType::Left));
+            }
+        }
         //Table name
         doc = doc
             .add_paragraph(paragraph!(
-                format!("Таблица {} - {} ({})", table_count, item.note, item.name),
+                format!("Таблица {} - {}", table_count + 1, caption),
                 AlignmentType::Right
             ))
             .page_orient(PageOrientationType::Landscape);
-        table_count += 1;
-
         //The actual table
-        doc = doc.add_table(Table::new(
-            header
-                .iter()
-                .cloned()
-                .chain(item.children.iter().map(|l| {
-                    TableRow::new(vec![
-                        cell!(l.datatype.to_owned()),
-                        cell!(l.code.to_owned()),
-                        cell!(l.note.to_owned()),
-                        cell!(l.msb.to_owned()),
-                        cell!(l.signed.to_owned()),
-                        cell!(l.bits.to_owned()),
-                        cell!(l.additional_data.to_owned()),
-                    ])
-                }))
-                .collect(),
-        ));
+        doc = doc.add_table(table);
+        table_count += 1;
     }
     doc.build().pack(file)?;
     Ok(())
 }
+
+//The tables of the section, then a numbered heading for each of its groups with their tables beneath
+fn section_blocks<'a>(section: &'a Section<'a>, number: &str, blocks: &mut Vec<Block<'a>>) {
+    blocks.extend(table_sources(&section.items).into_iter().map(Block::Table));
+    for (i, section) in section.sections.iter().enumerate() {
+        let number = match number.is_empty() {
+            true => (i + 1).to_string(),
+            false => format!("{}.{}", number, i + 1),
+        };
+        if let Some(group) = &section.group {
+            blocks.push(Block::Heading(number.clone(), group));
+        }
+        section_blocks(section, &number, blocks);
+    }
+}
+
+//`1.2 Title` at the outline level of its depth, followed by the description of the group
+fn heading(doc: Docx, number: &str, group: &Group) -> Docx {
+    let mut doc = doc.add_paragraph(
+        Paragraph::new()
+            .add_run(
+                Run::new()
+                    .add_text(format!("{} {}", number, group.title))
+                    .bold(),
+            )
+            .outline_lvl(number.matches('.').count())
+            .align(AlignmentType::Left),
+    );
+    for text in [&group.doc.brief, &group.doc.details] {
+        if !text.is_empty() {
+            doc = doc.add_paragraph(paragraph!(text, AlignmentType::Left));
+        }
+    }
+    doc
+}
+
+//Every item gets its own table, except for defines, which are grouped by their prefix
+fn table_sources<'a>(items: &[&'a DocumentationItem]) -> Vec<TableSource<'a>> {
+    let mut prefix_count = HashMap::new();
+    for item in items
+        .iter()
+        .filter(|i| i.r#type == DocumentationType::Define)
+    {
+        *prefix_count.entry(define_prefix(&item.name)).or_insert(0) += 1;
+    }
+    let mut sources = Vec::new();
+    for &item in items {
+        if item.r#type != DocumentationType::Define {
+            sources.push(TableSource::Item(item));
+            continue;
+        }
+        let prefix = match define_prefix(&item.name) {
+            prefix if prefix_count[prefix] > 1 => prefix,
+            _ => "",
+        };
+        //The table goes where the first define of the group is
+        let group = sources.iter_mut().find_map(|s| match s {
+            TableSource::Defines(p, defines) if p == prefix => Some(defines),
+            _ => None,
+        });
+        match group {
+            Some(defines) => defines.push(item),
+            None => sources.push(TableSource::Defines(prefix.to_owned(), vec![item])),
+        }
+    }
+    sources
+}
+
+//`MSG_ID_START` -> `MSG_ID_`, names without an underscore have no prefix
+fn define_prefix(name: &str) -> &str {
+    let name = name.split('(').next().unwrap_or_default();
+    match name.trim_end_matches('_').rfind('_') {
+        Some(end) => &name[..=end],
+        None => "",
+    }
+}
+
+fn item_caption(item: &DocumentationItem) -> String {
+    //The typedef name is what the code uses, the tag is only mentioned if it's different
+    let name = match (&item.alias, &item.tag) {
+        (Some(alias), Some(tag)) if alias != tag => {
+            format!("{}, {} {}", alias, item.r#type.keyword(), tag)
+        }
+        _ => item.name.clone(),
+    };
+    match item.size {
+        Some(size) => format!("{} ({}), размер {} байт", item.note, name, size),
+        None => format!("{} ({})", item.note, name),
+    }
+}
+
+fn item_table(
+    item: &DocumentationItem,
+    tables: &HashMap<String, usize>,
+    options: &ExportConfig,
+) -> Table {
+    //While I can make a system for defining the tables, it'd be quite a pain for this project
+    let mut columns = vec![
+        cell!("Название элемента структуры"),
+        cell!("Код параметра"),
+        cell!("Наименование параметра (сигнала)"),
+        cell!("ЦСР (ЦМР)"),
+        cell!("Знак"),
+        cell!("Размещение в разряде"),
+        cell!("Примечание"),
+    ];
+    let extra = ExtraColumns {
+        //Enumerators have a value of their own
+        value: item.r#type == DocumentationType::Enum,
+        custom: custom_columns(&item.children),
+        tables,
+        locations: options.locations,
+        layout: has_layout(&item.children),
+    };
+    if extra.value {
+        columns.insert(1, cell!("Значение"));
+    }
+    if extra.layout {
+        for (i, header) in ["Смещение, байт", "Размер, байт", "Заполнение, байт"]
+            .into_iter()
+            .enumerate()
+        {
+            columns.insert(1 + i, cell!(header));
+        }
+    }
+    //Configured annotation keys get their columns right before the remarks
+    for header in &extra.custom {
+        columns.insert(columns.len() - 1, cell!(header.to_owned()));
+    }
+    if extra.locations {
+        columns.push(cell!("Строка"));
+    }
+    let header_len = columns.len();
+    let header = TableRow::new(columns);
+
+    //All members of a union share the same memory, so they aren't consecutive fields
+    let union_row = (item.r#type == DocumentationType::Union).then(|| {
+        TableRow::new(vec![cell!(
+            "Поля объединения перекрываются: каждая строка - альтернативное представление одной и той же области памяти"
+        )
+        .grid_span(header_len)])
+    });
+
+    Table::new(
+        std::iter::once(header)
+            .chain(union_row)
+            .chain(child_rows(&item.children, "", &extra))
+            .collect(),
+    )
+}
+
+//The call interface: one row per parameter and one for the return value
+fn function_table(item: &DocumentationItem) -> Table {
+    let header = TableRow::new(vec![
+        cell!("Параметр"),
+        cell!("Тип"),
+        cell!("Направление"),
+        cell!("Описание"),
+    ]);
+    let direction = |direction: &str| match direction {
+        "in" => "Входной",
+        "out" => "Выходной",
+        "in,out" | "out,in" | "inout" => "Входной/выходной",
+        _ => "-",
+    };
+    let returns = TableRow::new(vec![
+        cell!("Возвращаемое значение"),
+        cell!(item.return_type.to_owned()),
+        cell!("-"),
+        cell!(match item.doc.returns.is_empty() {
+            true => "-".to_owned(),
+            false => item.doc.returns.clone(),
+        }),
+    ]);
+    Table::new(
+        std::iter::once(header)
+            .chain(item.parameters.iter().map(|p| {
+                TableRow::new(vec![
+                    cell!(p.name.to_owned()),
+                    cell!(p.datatype.to_owned()),
+                    cell!(direction(&p.direction)),
+                    cell!(match p.note.is_empty() {
+                        true => "-".to_owned(),
+                        false => p.note.clone(),
+                    }),
+                ])
+            }))
+            .chain(std::iter::once(returns))
+            .collect(),
+    )
+}
+
+fn defines_table(defines: &[&DocumentationItem], options: &ExportConfig) -> Table {
+    let mut header = vec![
+        cell!("Название константы"),
+        cell!("Значение"),
+        cell!("Описание"),
+    ];
+    if options.locations {
+        header.push(cell!("Строка"));
+    }
+    Table::new(
+        std::iter::once(TableRow::new(header))
+            .chain(defines.iter().map(|define| {
+                let mut cells = vec![
+                    cell!(define.name.to_owned()),
+                    cell!(define.value.to_owned()),
+                    cell!(define.note.to_owned()),
+                ];
+                if options.locations {
+                    cells.push(cell!(define.location.lines()));
+                }
+                TableRow::new(cells)
+            }))
+            .collect(),
+    )
+}
+
+//The columns only some of the tables have
+struct ExtraColumns<'a> {
+    value: bool,
+    //Headers of the annotation columns from the config, in the order they first appear
+    custom: Vec<String>,
+    //Item name -> its table number in this document
+    tables: &'a HashMap<String, usize>,
+    //The source lines go last, the file is in the caption
+    locations: bool,
+    //Offset, size and padding go right after the name
+    layout: bool,
+}
+
+fn has_layout(children: &[DocumentationItemChild]) -> bool {
+    children.iter().any(|child| {
+        child.layout.is_some()
+            || child
+                .nested
+                .as_ref()
+                .is_some_and(|nested| has_layout(&nested.children))
+    })
+}
+
+//Bitfields don't take whole bytes: `4`, `4 + 3 бит`, `5 бит`
+fn bytes(bits: usize) -> String {
+    match (bits / 8, bits % 8) {
+        (bytes, 0) => bytes.to_string(),
+        (0, bits) => format!("{} бит", bits),
+        (bytes, bits) => format!("{} + {} бит", bytes, bits),
+    }
+}
+
+fn custom_columns(children: &[DocumentationItemChild]) -> Vec<String> {
+    let mut headers = Vec::new();
+    for child in children {
+        for field in &child.annotation.fields {
+            if let Column::Custom(header) = &field.column {
+                if !headers.contains(header) {
+                    headers.push(header.clone());
+                }
+            }
+        }
+        if let Some(nested) = &child.nested {
+            for header in custom_columns(&nested.children) {
+                if !headers.contains(&header) {
+                    headers.push(header);
+                }
+            }
+        }
+    }
+    headers
+}
+
+//Fields of nested structs/unions follow their parent field as `parent.field` rows
+fn child_rows(
+    children: &[DocumentationItemChild],
+    prefix: &str,
+    extra: &ExtraColumns,
+) -> Vec<TableRow> {
+    let mut rows = Vec::new();
+    for l in children {
+        let path = field_path(prefix, &l.name);
+        let mut cells = vec![
+            cell!(l.declaration_named(&path)),
+            cell!(l.code.to_owned()),
+            cell!(l.note.to_owned()),
+            cell!(scale(l)),
+            cell!(sign(l)),
+            cell!(bits(l)),
+            cell!(remarks(l, extra.tables)),
+        ];
+        if extra.value {
+            cells.insert(
+                1,
+                cell!(l.value.map_or_else(|| "-".to_owned(), |v| v.to_string())),
+            );
+        }
+        if extra.layout {
+            let layout = [
+                l.layout.map(|l| l.offset),
+                l.layout.map(|l| l.size),
+                l.layout.map(|l| l.padding),
+            ];
+            for (i, bits) in layout.into_iter().enumerate() {
+                cells.insert(1 + i, cell!(bits.map_or_else(|| "-".to_owned(), bytes)));
+            }
+        }
+        for header in &extra.custom {
+            let text = l
+                .annotation
+                .column_text(&Column::Custom(header.clone()))
+                .unwrap_or("-".to_owned());
+            cells.insert(cells.len() - 1, cell!(text));
+        }
+        if extra.locations {
+            cells.push(cell!(l.location.lines()));
+        }
+        rows.push(TableRow::new(cells));
+        if let Some(nested) = &l.nested {
+            rows.extend(child_rows(&nested.children, &path, extra));
+        }
+    }
+    rows
+}
+
+//`parent.field`, anonymous members are accessed as if they were fields of the parent
+fn field_path(prefix: &str, name: &str) -> String {
+    match (prefix.is_empty(), name.is_empty()) {
+        (_, true) => prefix.to_owned(),
+        (true, false) => name.to_owned(),
+        (false, false) => format!("{}.{}", prefix, name),
+    }
+}
+
+//`ЦСР (ЦМР)`, the missing one is computed from the width when possible
+fn scale(child: &DocumentationItemChild) -> String {
+    let annotation = &child.annotation;
+    let scale = match annotation.scale {
+        Some(scale) => scale,
+        None => return child.msb.clone(),
+    };
+    match (annotation.msb(), annotation.lsb(), scale.kind) {
+        (Some(msb), Some(lsb), _) => format!("{} ({})", msb, lsb),
+        (_, _, ScaleKind::Msb) => format!("ЦСР: {}", scale.value),
+        (_, _, ScaleKind::Lsb) => format!("ЦМР: {}", scale.value),
+    }
+}
+
+fn sign(child: &DocumentationItemChild) -> String {
+    match child.annotation.sign {
+        Some(Sign::Signed) => "Да".to_owned(),
+        Some(Sign::Unsigned) => "Нет".to_owned(),
+        Some(Sign::Bit(bit)) => bit.to_string(),
+        None => child.signed.clone(),
+    }
+}
+
+fn bits(child: &DocumentationItemChild) -> String {
+    match child.annotation.bits {
+        Some(bits) if bits.first == bits.last => bits.first.to_string(),
+        Some(bits) => format!("{}..{}", bits.first, bits.last),
+        None => child.bits.clone(),
+    }
+}
+
+//The additional data from the annotation block together with the notes and warnings from the comment
+fn remarks(child: &DocumentationItemChild, tables: &HashMap<String, usize>) -> String {
+    let mut remarks = Vec::new();
+    if child.additional_data != "-" {
+        remarks.push(child.additional_data.clone());
+    }
+    if let Some(type_ref) = &child.type_ref {
+        remarks.push(match tables.get(&type_ref.name) {
+            Some(table) => format!("См. таблицу {}", table),
+            None => format!(
+                "См. {} ({})",
+                type_ref.name,
+                type_ref
+                    .file
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+            ),
+        });
+    }
+    remarks.extend(child.doc.notes.iter().cloned());
+    remarks.extend(
+        child
+            .doc
+            .warnings
+            .iter()
+            .map(|warning| format!("Внимание: {}", warning)),
+    );
+    match child.doc.deprecated.as_deref() {
+        Some("") => remarks.push("Устарело".to_owned()),
+        Some(deprecated) => remarks.push(format!("Устарело: {}", deprecated)),
+        None => {}
+    }
+    if remarks.is_empty() {
+        return "-".to_owned();
+    }
+    remarks.join("; ")
+}
+
+#[test]
+fn bytes_test() {
+    assert_eq!(bytes(32), "4");
+    assert_eq!(bytes(35), "4 + 3 бит");
+    assert_eq!(bytes(5), "5 бит");
+    assert_eq!(bytes(0), "0");
+}
+
+#[test]
+fn define_prefix_test() {
+    assert_eq!(define_prefix("MSG_ID_START"), "MSG_ID_");
+    assert_eq!(define_prefix("MAX(a, b)"), "");
+    assert_eq!(define_prefix("stuff"), "");
+    assert_eq!(define_prefix("more_stuff"), "more_");
+    assert_eq!(define_prefix("GUARD_H_"), "GUARD_");
+}
+
+#[test]
+fn field_path_test() {
+    let data = crate::parser::parse_source(
+        "//! Outer\n\
+         struct outer {\n\
+           unsigned int id : 4; //!< Id\n\
+           struct {\n\
+             const char *name; //!< Name\n\
+           } inner; //!< Inner\n\
+         };",
+    );
+    let id = &data.items[0].children[0];
+    let inner = &data.items[0].children[1];
+    let name = &inner.nested.as_ref().unwrap().children[0];
+    //Every row is the declaration as written, only the name turns into a path
+    assert_eq!(
+        id.declaration_named(&field_path("", &id.name)),
+        "unsigned int id : 4"
+    );
+    assert_eq!(
+        inner.declaration_named(&field_path("", &inner.name)),
+        "struct { ... } inner"
+    );
+    assert_eq!(
+        name.declaration_named(&field_path("inner", &name.name)),
+        "const char *inner.name"
+    );
+    assert_eq!(field_path("inner", ""), "inner");
+}
@@ -0,0 +1,149 @@
+//The GPLv3 License (GPLv3)
+//
+//Copyright (c) 2023 Ciubix8513
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    doc_comment::DocComment,
+    parser::{DocumentationData, DocumentationItem, Location},
+};
+use std::collections::HashSet;
+
+//`@defgroup name Title` or `@addtogroup name Title`
+#[derive(Default, Debug, Clone)]
+pub struct Group {
+    pub name: String,
+    //The name if no title was given
+    pub title: String,
+    //The rest of the comment that defined the group
+    pub doc: DocComment,
+    //`@ingroup parent`, or the group whose `@{` block the definition is in
+    pub parent: Option<String>,
+    pub location: Location,
+}
+
+//A group of a file with the items in it and its subgroups, the items keep their order from the file
+#[derive(Default, Debug, Clone)]
+pub struct Section<'a> {
+    //None for the items outside of any group
+    pub group: Option<Group>,
+    pub items: Vec<&'a DocumentationItem>,
+    pub sections: Vec<Section<'a>>,
+}
+
+//The root section has the items outside of any group and the top level groups,
+//groups without any items in them or in their subgroups are left out
+pub fn sections(data: &DocumentationData) -> Section<'_> {
+    let mut groups = data.groups.clone();
+    //Groups defined in other files only have a name
+    for name in data.items.iter().filter_map(|i| i.group.as_ref()) {
+        if !groups.iter().any(|g| &g.name == name) {
+            groups.push(Group {
+                name: name.clone(),
+                title: name.clone(),
+                ..Default::default()
+            });
+        }
+    }
+    let mut visited = HashSet::new();
+    let mut root = Section {
+        group: None,
+        items: data.items.iter().filter(|i| i.group.is_none()).collect(),
+        sections: Vec::new(),
+    };
+    let is_top = |group: &Group| match &group.parent {
+        Some(parent) => !groups.iter().any(|g| &g.name == parent),
+        None => true,
+    };
+    for group in groups.iter().filter(|g| is_top(g)) {
+        root.sections
+            .extend(section(group, &groups, data, &mut visited));
+    }
+    //Whatever is left is in a loop of parents
+    for group in &groups {
+        if !visited.contains(&group.name) {
+            root.sections
+                .extend(section(group, &groups, data, &mut visited));
+        }
+    }
+    root
+}
+
+fn section<'a>(
+    group: &Group,
+    groups: &[Group],
+    data: &'a DocumentationData,
+    visited: &mut HashSet<String>,
+) -> Option<Section<'a>> {
+    if !visited.insert(group.name.clone()) {
+        return None;
+    }
+    let sections = groups
+        .iter()
+        .filter(|g| g.parent.as_ref() == Some(&group.name))
+        .filter_map(|g| section(g, groups, data, visited))
+        .collect::<Vec<_>>();
+    let items = data
+        .items
+        .iter()
+        .filter(|i| i.group.as_ref() == Some(&group.name))
+        .collect::<Vec<_>>();
+    (!items.is_empty() || !sections.is_empty()).then(|| Section {
+        group: Some(group.clone()),
+        items,
+        sections,
+    })
+}
+
+#[test]
+fn sections_test() {
+    use crate::parser::parse_file;
+    use std::path::PathBuf;
+    let data = parse_file(PathBuf::from("test_data/test23.h"), encoding::all::UTF_8).unwrap();
+    let root = sections(&data);
+    let names = |section: &Section| {
+        section
+            .items
+            .iter()
+            .map(|i| i.name.clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(&root), ["VERSION"]);
+    assert_eq!(root.sections.len(), 2);
+
+    let protocol = &root.sections[0];
+    assert_eq!(protocol.group.as_ref().unwrap().title, "Protocol");
+    assert_eq!(names(protocol), ["header_t"]);
+    //Both by `@ingroup` and by being defined in the block of `protocol`
+    let subgroups = protocol
+        .sections
+        .iter()
+        .map(|s| s.group.as_ref().unwrap().title.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(subgroups, ["Commands", "Replies"]);
+    assert_eq!(names(&protocol.sections[0]), ["command_t", "CMD_MAX"]);
+    assert_eq!(names(&protocol.sections[1]), ["reply_t", "send"]);
+
+    //Defined elsewhere, only the name is known
+    let external = &root.sections[1];
+    assert_eq!(external.group.as_ref().unwrap().title, "external");
+    assert_eq!(names(external), ["extra_t"]);
+
+    //The last `//@}` closes nothing
+    assert_eq!(
+        data.diagnostics.last().unwrap().message,
+        "`@}` without a matching `@{` is ignored"
+    );
+}
@@ -17,22 +17,30 @@
 
 use std::path::{PathBuf, Path};
 
-use encoding::Encoding;
+use doxygen_gen::{export_doc, ExportConfig, SymbolTable};
 
-use crate::{parser::parse_file, exporter::export_doc};
-
-
-
-pub fn process_file(r#in: PathBuf, out: &Path, encoding: &dyn Encoding) {
-    //I don't think I need all this error checking, but i'm just gonna leave it 
-    let data = parse_file(r#in.clone(), encoding.to_owned());
-    if data.is_err() {
-        println!("Could not parse {}", r#in.display());
+pub fn process_file(r#in: PathBuf, out: &Path, symbols: &SymbolTable, options: &ExportConfig) {
+    //The file was already parsed when the symbol table was loaded
+    let data = symbols.data(&r#in);
+    if data.is_none() {
+        match symbols.error(&r#in) {
+            Some(e) => println!("Could not parse {}: {}", r#in.display(), e),
+            None => println!("Could not parse {}", r#in.display()),
+        }
+        return;
+    }
+    //Field types can come from any of the parsed files
+    let mut data = data.unwrap().clone();
+    symbols.resolve(&mut data);
+    if let Some(encoding) = &data.encoding {
+        println!("{}: detected {}", r#in.display(), encoding);
+    }
+    for diagnostic in data.diagnostics.iter() {
+        println!("{}", diagnostic);
     }
-    let data = data.unwrap();
     let mut out = out.join(r#in.file_name().unwrap());
     out.set_extension("docx");
-    if let Err(e) = export_doc(data, out.clone()) {
+    if let Err(e) = export_doc(data, out.clone(), options) {
         println!("{:#?}", out);
         println!("{:#?}", e);
     }
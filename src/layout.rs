@@ -0,0 +1,444 @@
+//The GPLv3 License (GPLv3)
+//
+//Copyright (c) 2023 Ciubix8513
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::parser::{DocumentationItem, DocumentationItemChild, DocumentationType};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+//Typedefs of structs of typedefs, anything deeper is most likely a loop
+const MAX_DEPTH: usize = 32;
+
+pub const PRESETS: [&str; 3] = ["arm-eabi", "x86-64-sysv", "dsp16"];
+
+//Where a field is, in bits since bitfields don't fit bytes
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldLayout {
+    //From the start of the item, the fields of nested structs included
+    pub offset: usize,
+    pub size: usize,
+    //The unused bits up to the next member, or up to the end of the item for the last one
+    pub padding: usize,
+}
+
+//`sizeof` and `_Alignof` of a type, in bytes
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TypeLayout {
+    pub size: usize,
+    pub align: usize,
+}
+
+//The sizes and alignments of the primitive types on a target
+#[derive(Debug, Clone)]
+pub struct Abi {
+    pub name: String,
+    //By the canonical names, `unsigned long int` is `long` and `uint16_t` is `int16_t`
+    pub types: HashMap<String, TypeLayout>,
+    pub pointer: TypeLayout,
+    //The underlying type of the enums
+    pub enumeration: TypeLayout,
+}
+
+impl Abi {
+    pub fn preset(name: &str) -> Option<Abi> {
+        //char, short, int, long, long long, float, double, long double and pointers,
+        //everything is aligned to its size, up to the max alignment
+        let (sizes, max_align) = match name {
+            "arm-eabi" => ([1, 2, 4, 4, 8, 4, 8, 8, 4], 8),
+            "x86-64-sysv" => ([1, 2, 4, 8, 8, 4, 8, 16, 8], 16),
+            //16-bit ints and pointers, nothing needs more than a word of alignment
+            "dsp16" => ([1, 2, 2, 4, 8, 4, 4, 4, 2], 2),
+            _ => return None,
+        };
+        let layout = |size: usize| TypeLayout {
+            size,
+            align: size.min(max_align),
+        };
+        let [char, short, int, long, long_long, float, double, long_double, pointer] =
+            sizes.map(layout);
+        let types = [
+            ("char", char),
+            ("_Bool", char),
+            ("short", short),
+            ("int", int),
+            ("long", long),
+            ("long long", long_long),
+            ("float", float),
+            ("double", double),
+            ("long double", long_double),
+            ("int8_t", layout(1)),
+            ("int16_t", layout(2)),
+            ("int32_t", layout(4)),
+            ("int64_t", layout(8)),
+            ("size_t", pointer),
+            ("ptrdiff_t", pointer),
+            ("intptr_t", pointer),
+        ];
+        Some(Abi {
+            name: name.to_owned(),
+            types: types
+                .into_iter()
+                .map(|(name, layout)| (name.to_owned(), layout))
+                .collect(),
+            pointer,
+            enumeration: int,
+        })
+    }
+
+    //`pointer` and `enum` are the pointers and the enums, anything else is a type name
+    pub fn set(&mut self, name: &str, layout: TypeLayout) {
+        match name {
+            "pointer" => self.pointer = layout,
+            "enum" => self.enumeration = layout,
+            _ => {
+                self.types.insert(canonical(name), layout);
+            }
+        }
+    }
+}
+
+//What the layout needs to know about the rest of the headers
+pub trait Types {
+    //A documented struct, union or enum by any of its names
+    fn item(&self, name: &str) -> Option<&DocumentationItem>;
+    fn typedef(&self, name: &str) -> Option<&DocumentationItemChild>;
+    //Array lengths and bitfield widths
+    fn evaluate(&self, expression: &str) -> Option<i64>;
+}
+
+//Fills in the layout of the children and the size of the item, nothing is filled in if a type is unknown
+pub fn layout(item: &mut DocumentationItem, abi: &Abi, types: &dyn Types) {
+    let layout = aggregate(item, abi, types, 0);
+    item.size = layout.map(|l| l.size);
+    item.align = layout.map(|l| l.align);
+}
+
+//The members of an item in the declaration order
+enum Member {
+    Child(usize),
+    Undocumented(usize),
+}
+
+fn aggregate(
+    item: &mut DocumentationItem,
+    abi: &Abi,
+    types: &dyn Types,
+    depth: usize,
+) -> Option<TypeLayout> {
+    match item.r#type {
+        DocumentationType::Enum => return Some(abi.enumeration),
+        DocumentationType::Struct | DocumentationType::Union => {}
+        _ => return None,
+    }
+    if depth > MAX_DEPTH {
+        return None;
+    }
+    let union = item.r#type == DocumentationType::Union;
+    let pack = item.pack.unwrap_or(usize::MAX);
+    let mut members = Vec::new();
+    for i in 0..=item.children.len() {
+        members.extend(
+            (0..item.undocumented.len())
+                .filter(|u| item.undocumented[*u].0 == i)
+                .map(Member::Undocumented),
+        );
+        if i < item.children.len() {
+            members.push(Member::Child(i));
+        }
+    }
+
+    //(offset, size) in bits
+    let mut placed = Vec::new();
+    let mut offset = 0;
+    let mut end = 0;
+    let mut align = 1;
+    for member in &members {
+        let child = match member {
+            Member::Child(i) => &mut item.children[*i],
+            Member::Undocumented(i) => &mut item.undocumented[*i].1,
+        };
+        let field = field_layout(child, abi, types, depth)?;
+        let field_align = field.align.min(pack);
+        let bits = match &child.bit_width {
+            Some(width) => Some(usize::try_from(types.evaluate(width)?).ok()?),
+            None => None,
+        };
+        let unit = field.align * 8;
+        let start = match bits {
+            _ if union => 0,
+            //`int : 0;` moves the next bitfield to a new unit
+            Some(0) => align_up(offset, unit),
+            //Bitfields don't cross the units of their type, unless packed
+            Some(bits)
+                if field_align == field.align && offset / unit != (offset + bits - 1) / unit =>
+            {
+                align_up(offset, unit)
+            }
+            Some(_) => offset,
+            None => align_up(offset, field_align * 8),
+        };
+        let size = bits.unwrap_or(field.size * 8);
+        align = align.max(field_align);
+        offset = start + size;
+        end = end.max(offset);
+        placed.push((start, size));
+    }
+    let size = align_up(end, align * 8);
+
+    for (i, member) in members.iter().enumerate() {
+        let Member::Child(child) = member else {
+            continue;
+        };
+        let (start, field_size) = placed[i];
+        let next = match placed.get(i + 1) {
+            Some((next, _)) if !union => *next,
+            _ => size,
+        };
+        let child = &mut item.children[*child];
+        child.layout = Some(FieldLayout {
+            offset: start,
+            size: field_size,
+            padding: next.saturating_sub(start + field_size),
+        });
+        //The nested fields were placed from the start of their struct
+        if let Some(nested) = child.nested.as_deref_mut() {
+            shift(nested, start);
+        }
+    }
+    Some(TypeLayout {
+        size: size / 8,
+        align,
+    })
+}
+
+fn shift(item: &mut DocumentationItem, offset: usize) {
+    for child in item.children.iter_mut() {
+        if let Some(layout) = child.layout.as_mut() {
+            layout.offset += offset;
+        }
+        if let Some(nested) = child.nested.as_deref_mut() {
+            shift(nested, offset);
+        }
+    }
+}
+
+//The whole field, arrays included
+fn field_layout(
+    child: &mut DocumentationItemChild,
+    abi: &Abi,
+    types: &dyn Types,
+    depth: usize,
+) -> Option<TypeLayout> {
+    let base = if child.pointer_depth > 0 {
+        abi.pointer
+    } else if let Some(nested) = child.nested.as_deref_mut() {
+        let layout = aggregate(nested, abi, types, depth + 1)?;
+        nested.size = Some(layout.size);
+        nested.align = Some(layout.align);
+        layout
+    } else {
+        named(&child.base_type, abi, types, depth + 1)?
+    };
+    let mut count = 1;
+    for dimension in &child.array_dims {
+        count *= usize::try_from(types.evaluate(dimension)?).ok()?;
+    }
+    Some(TypeLayout {
+        size: base.size * count,
+        align: base.align,
+    })
+}
+
+fn named(name: &str, abi: &Abi, types: &dyn Types, depth: usize) -> Option<TypeLayout> {
+    if depth > MAX_DEPTH {
+        return None;
+    }
+    if let Some(layout) = abi.types.get(&canonical(name)) {
+        return Some(*layout);
+    }
+    //The typedefs are by the name as written, `typedef unsigned short uint24_t;` isn't `int24_t`
+    let written = written(name);
+    if let Some(typedef) = types.typedef(&written) {
+        return field_layout(&mut typedef.clone(), abi, types, depth + 1);
+    }
+    if let Some(item) = types.item(&written) {
+        return aggregate(&mut item.clone(), abi, types, depth + 1);
+    }
+    //Enums are ints even if they aren't documented
+    name.split_whitespace()
+        .any(|w| w == "enum")
+        .then_some(abi.enumeration)
+}
+
+//`struct s` is `s` and `const word_t` is `word_t`
+fn written(name: &str) -> String {
+    name.split_whitespace()
+        .filter(|w| !matches!(*w, "struct" | "union" | "enum" | "const" | "volatile"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+//The names of the ABI table, `unsigned long int` is `long`, `uint8_t` is `int8_t` and `struct s` is `s`
+fn canonical(name: &str) -> String {
+    let words = name
+        .split_whitespace()
+        .filter(|w| {
+            !matches!(
+                *w,
+                "signed" | "unsigned" | "struct" | "union" | "enum" | "const" | "volatile"
+            )
+        })
+        .collect::<Vec<_>>();
+    let builtin = |w: &&str| {
+        matches!(
+            *w,
+            "char" | "short" | "int" | "long" | "float" | "double" | "_Bool" | "bool"
+        )
+    };
+    if !words.iter().all(builtin) {
+        let name = words.join(" ");
+        return match name.strip_prefix('u') {
+            Some(signed) if signed.starts_with("int") && signed.ends_with("_t") => {
+                signed.to_owned()
+            }
+            _ => name,
+        };
+    }
+    let longs = words.iter().filter(|w| **w == "long").count();
+    let name = if words.contains(&"char") {
+        "char"
+    } else if words.contains(&"short") {
+        "short"
+    } else if words.contains(&"float") {
+        "float"
+    } else if words.contains(&"double") {
+        match longs {
+            0 => "double",
+            _ => "long double",
+        }
+    } else if words.contains(&"_Bool") || words.contains(&"bool") {
+        "_Bool"
+    } else {
+        //`unsigned` alone is an int too
+        match longs {
+            0 => "int",
+            1 => "long",
+            _ => "long long",
+        }
+    };
+    name.to_owned()
+}
+
+fn align_up(offset: usize, align: usize) -> usize {
+    offset.div_ceil(align.max(1)) * align.max(1)
+}
+
+#[test]
+fn canonical_test() {
+    assert_eq!(canonical("unsigned long int"), "long");
+    assert_eq!(canonical("long long unsigned"), "long long");
+    assert_eq!(canonical("unsigned"), "int");
+    assert_eq!(canonical("long double"), "long double");
+    assert_eq!(canonical("uint16_t"), "int16_t");
+    assert_eq!(canonical("struct msg_header"), "msg_header");
+}
+
+#[test]
+fn struct_layout_test() {
+    use crate::{config::Config, symbols::SymbolTable};
+    use std::path::PathBuf;
+    let file = PathBuf::from("test_data/test22.h");
+    let mut config = Config::default();
+    config.layout.abi = Some("arm-eabi".to_owned());
+    let table = SymbolTable::load(
+        std::slice::from_ref(&file),
+        encoding::all::UTF_8,
+        &config,
+    );
+    let mut data = table.data(&file).unwrap().clone();
+    table.resolve(&mut data);
+
+    let bytes = |offset: usize, size: usize, padding: usize| FieldLayout {
+        offset: offset * 8,
+        size: size * 8,
+        padding: padding * 8,
+    };
+    let mixed = &data.items[0];
+    let layouts = mixed
+        .children
+        .iter()
+        .map(|c| c.layout.unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(layouts[0], bytes(0, 1, 3));
+    assert_eq!(layouts[1], bytes(4, 4, 0));
+    //The undocumented flags are at 8, the bitfield unit of `b` would be crossed
+    assert_eq!(
+        layouts[2],
+        FieldLayout {
+            offset: 72,
+            size: 3,
+            padding: 5
+        }
+    );
+    assert_eq!(
+        layouts[3],
+        FieldLayout {
+            offset: 80,
+            size: 14,
+            padding: 2
+        }
+    );
+    assert_eq!(layouts[4], bytes(12, 8, 0));
+    assert_eq!(layouts[5], bytes(20, 2, 2));
+    assert_eq!(layouts[6], bytes(24, 4, 4));
+    assert_eq!(layouts[7], bytes(32, 8, 0));
+    assert_eq!((mixed.size, mixed.align), (Some(40), Some(8)));
+
+    assert_eq!(data.items[1].size, Some(5));
+    assert_eq!(data.items[1].children[1].layout, Some(bytes(1, 4, 0)));
+    assert_eq!(data.items[2].size, Some(3));
+
+    let overlay = &data.items[3];
+    assert_eq!(overlay.size, Some(4));
+    assert_eq!(overlay.children[0].layout, Some(bytes(0, 4, 0)));
+    let parts = overlay.children[1].nested.as_ref().unwrap();
+    assert_eq!(parts.children[1].layout, Some(bytes(2, 2, 0)));
+
+    let unknown = &data.items[4];
+    assert_eq!(unknown.size, None);
+    assert!(unknown.children.iter().all(|c| c.layout.is_none()));
+
+    //A typedef named like a fixed width type is looked up by its own name
+    let own = &data.items[5];
+    assert_eq!(own.children[0].layout, Some(bytes(0, 2, 0)));
+    assert_eq!(own.size, Some(4));
+
+    //The types of the members don't have to be documented
+    let outer = &data.items[6];
+    assert_eq!(outer.children[1].layout, Some(bytes(4, 4, 0)));
+    assert_eq!(outer.size, Some(12));
+
+    //16-bit ints and pointers, nothing aligned to more than 2
+    config.layout.abi = Some("dsp16".to_owned());
+    let table = SymbolTable::load(
+        std::slice::from_ref(&file),
+        encoding::all::UTF_8,
+        &config,
+    );
+    let mut data = table.data(&file).unwrap().clone();
+    table.resolve(&mut data);
+    assert_eq!(data.items[0].size, Some(26));
+}
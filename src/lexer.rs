@@ -0,0 +1,463 @@
+//The GPLv3 License (GPLv3)
+//
+//Copyright (c) 2023 Ciubix8513
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//Multi character punctuators, longest first so that `<<=` wins over `<<`
+const PUNCTUATORS: [&str; 23] = [
+    "<<=", ">>=", "...", "->", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+=",
+    "-=", "*=", "/=", "%=", "&=", "|=", "^=", "##",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentKind {
+    //A regular comment, not interesting for the documentation
+    Plain,
+    //`//!`, `///`, `/** */` or `/*! */` comment, documents the next declaration
+    Doc,
+    //`//!<`, `///<`, `/**< */` or `/*!< */` comment, documents the previous declaration
+    DocTrailing,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenKind {
+    Identifier,
+    Number,
+    String,
+    Char,
+    Punct,
+    //The text of the comment is stored without the comment markers
+    Comment(CommentKind),
+    //A whole preprocessor line, continuations included, comments excluded
+    Preprocessor,
+}
+
+//Position of a token in the decoded source, lines and columns start at 1
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+    pub span: Span,
+}
+
+impl Token {
+    pub fn is_punct(&self, punct: &str) -> bool {
+        self.kind == TokenKind::Punct && self.text == punct
+    }
+
+    pub fn is_ident(&self, ident: &str) -> bool {
+        self.kind == TokenKind::Identifier && self.text == ident
+    }
+
+    pub fn is_comment(&self) -> bool {
+        matches!(self.kind, TokenKind::Comment(_))
+    }
+}
+
+struct Lexer<'a> {
+    source: &'a str,
+    pos: usize,
+    line: usize,
+    line_start: usize,
+    //Only whitespace has been seen on the current line, used to detect preprocessor lines
+    at_line_start: bool,
+    tokens: Vec<Token>,
+}
+
+//Strips the ` * ` decoration off every line of a block comment
+fn block_text(body: &str) -> String {
+    body.lines()
+        .map(|line| {
+            let line = line.trim();
+            match line.strip_prefix('*') {
+                Some(line) => line.trim(),
+                None => line,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_owned()
+}
+
+pub fn tokenize(source: &str) -> Vec<Token> {
+    let mut lexer = Lexer {
+        source,
+        pos: 0,
+        line: 1,
+        line_start: 0,
+        at_line_start: true,
+        tokens: Vec::new(),
+    };
+    lexer.run();
+    lexer.tokens
+}
+
+impl<'a> Lexer<'a> {
+    fn peek(&self) -> Option<char> {
+        self.source[self.pos..].chars().next()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.source[self.pos..].chars().nth(offset)
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.source[self.pos..]
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.line_start = self.pos;
+            self.at_line_start = true;
+        }
+        Some(c)
+    }
+
+    fn span_here(&self) -> Span {
+        Span {
+            start: self.pos,
+            end: self.pos,
+            line: self.line,
+            column: self.source[self.line_start..self.pos].chars().count() + 1,
+        }
+    }
+
+    fn push(&mut self, kind: TokenKind, text: String, mut span: Span) {
+        span.end = self.pos;
+        self.tokens.push(Token { kind, text, span });
+    }
+
+    fn run(&mut self) {
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() {
+                self.bump();
+                continue;
+            }
+            if self.rest().starts_with("//") || self.rest().starts_with("/*") {
+                self.comment();
+                continue;
+            }
+            if c == '#' && self.at_line_start {
+                self.preprocessor();
+                continue;
+            }
+            self.at_line_start = false;
+            let span = self.span_here();
+            if c.is_alphabetic() || c == '_' {
+                let text = self.take_while(|c| c.is_alphanumeric() || c == '_');
+                //String and char literals with an encoding prefix
+                if matches!(text.as_str(), "L" | "u" | "U" | "u8") && self.peek() == Some('"') {
+                    let literal = self.quoted('"');
+                    self.push(TokenKind::String, text + &literal, span);
+                } else if matches!(text.as_str(), "L" | "u" | "U") && self.peek() == Some('\'') {
+                    let literal = self.quoted('\'');
+                    self.push(TokenKind::Char, text + &literal, span);
+                } else {
+                    self.push(TokenKind::Identifier, text, span);
+                }
+            } else if c.is_ascii_digit()
+                || (c == '.' && self.peek_at(1).is_some_and(|c| c.is_ascii_digit()))
+            {
+                let text = self.number();
+                self.push(TokenKind::Number, text, span);
+            } else if c == '"' {
+                let text = self.quoted('"');
+                self.push(TokenKind::String, text, span);
+            } else if c == '\'' {
+                let text = self.quoted('\'');
+                self.push(TokenKind::Char, text, span);
+            } else {
+                let text = match PUNCTUATORS.iter().find(|p| self.rest().starts_with(*p)) {
+                    Some(p) => p.to_string(),
+                    None => c.to_string(),
+                };
+                self.pos += text.len();
+                self.push(TokenKind::Punct, text, span);
+            }
+        }
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(&f) {
+            self.bump();
+        }
+        self.source[start..self.pos].to_owned()
+    }
+
+    fn number(&mut self) -> String {
+        let start = self.pos;
+        let hex = self.rest().starts_with("0x") || self.rest().starts_with("0X");
+        while let Some(c) = self.peek() {
+            //Exponent signs are part of the number (1e-5, 0x1p+3)
+            let exponent = match self.source[start..self.pos].chars().last() {
+                Some('e' | 'E') => !hex,
+                Some('p' | 'P') => hex,
+                _ => false,
+            };
+            if !(((c == '+' || c == '-') && exponent)
+                || c.is_alphanumeric()
+                || c == '.'
+                || c == '_')
+            {
+                break;
+            }
+            self.bump();
+        }
+        self.source[start..self.pos].to_owned()
+    }
+
+    fn quoted(&mut self, quote: char) -> String {
+        let start = self.pos;
+        self.bump();
+        while let Some(c) = self.peek() {
+            if c == '\\' {
+                self.bump();
+                self.bump();
+            } else if c == '\n' {
+                //Unterminated literal, don't swallow the rest of the file
+                break;
+            } else {
+                self.bump();
+                if c == quote {
+                    break;
+                }
+            }
+        }
+        self.source[start..self.pos].to_owned()
+    }
+
+    fn comment(&mut self) {
+        let span = self.span_here();
+        if self.rest().starts_with("//") {
+            let end = self.rest().find('\n').unwrap_or(self.rest().len());
+            let raw = &self.rest()[..end];
+            self.pos += end;
+            let (kind, text) = if let Some(text) = raw
+                .strip_prefix("//!<")
+                .or_else(|| raw.strip_prefix("///<"))
+            {
+                (CommentKind::DocTrailing, text)
+            } else if let Some(text) = raw.strip_prefix("//!").or_else(|| {
+                raw.strip_prefix("///")
+                    .filter(|text| !text.starts_with('/'))
+            }) {
+                (CommentKind::Doc, text)
+            } else {
+                (CommentKind::Plain, &raw[2..])
+            };
+            let text = text.trim().to_owned();
+
+            //Consecutive `///` or `//!` lines make up a single block
+            if let Some(previous) = self.tokens.last_mut() {
+                let between = &self.source[previous.span.end..span.start];
+                if kind != CommentKind::Plain
+                    && previous.kind == TokenKind::Comment(kind)
+                    && self.source[previous.span.start..].starts_with("//")
+                    && between.trim().is_empty()
+                    && between.matches('\n').count() == 1
+                {
+                    previous.text = format!("{}\n{}", previous.text, text);
+                    previous.span.end = self.pos;
+                    return;
+                }
+            }
+            self.push(TokenKind::Comment(kind), text, span);
+        } else {
+            let end = self.rest()[2..]
+                .find("*/")
+                .map_or(self.rest().len(), |e| e + 4);
+            let raw = &self.rest()[..end];
+            //Keep the line counter right
+            for _ in raw.chars() {
+                self.bump();
+            }
+            let body = raw
+                .strip_prefix("/*")
+                .map(|t| t.strip_suffix("*/").unwrap_or(t))
+                .unwrap_or(raw);
+            let (kind, body) =
+                if let Some(body) = body.strip_prefix("*<").or_else(|| body.strip_prefix("!<")) {
+                    (CommentKind::DocTrailing, body)
+                } else if let Some(body) = body.strip_prefix('!').or_else(|| {
+                    //`/***********` banners aren't documentation
+                    body.strip_prefix('*').filter(|body| !body.starts_with('*'))
+                }) {
+                    (CommentKind::Doc, body)
+                } else {
+                    (CommentKind::Plain, body)
+                };
+            self.push(TokenKind::Comment(kind), block_text(body), span);
+            //A block comment doesn't end the "only whitespace so far" state
+        }
+    }
+
+    fn preprocessor(&mut self) {
+        let span = self.span_here();
+        let mut text = String::new();
+        let mut comments = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '\n' {
+                break;
+            }
+            if c == '\\' && matches!(self.peek_at(1), Some('\n')) {
+                self.bump();
+                self.bump();
+                text.push(' ');
+                continue;
+            }
+            if c == '\\' && self.rest().starts_with("\\\r\n") {
+                self.bump();
+                self.bump();
+                self.bump();
+                text.push(' ');
+                continue;
+            }
+            if self.rest().starts_with("//") || self.rest().starts_with("/*") {
+                let line = self.line;
+                let before = self.tokens.len();
+                self.comment();
+                comments.extend(self.tokens.drain(before..));
+                //A block comment may end the directive if it spans lines
+                if self.line != line {
+                    break;
+                }
+                text.push(' ');
+                continue;
+            }
+            if c == '"' || c == '\'' {
+                text += &self.quoted(c);
+                continue;
+            }
+            text.push(c);
+            self.bump();
+        }
+        let end = self.pos;
+        self.tokens.push(Token {
+            kind: TokenKind::Preprocessor,
+            text: text.trim().to_owned(),
+            span: Span { end, ..span },
+        });
+        self.tokens.extend(comments);
+    }
+}
+
+#[test]
+fn tokenize_field_test() {
+    let tokens = tokenize("  int a; //!< This is A\n");
+    let kinds = tokens.iter().map(|t| t.kind.clone()).collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        vec![
+            TokenKind::Identifier,
+            TokenKind::Identifier,
+            TokenKind::Punct,
+            TokenKind::Comment(CommentKind::DocTrailing)
+        ]
+    );
+    assert_eq!(tokens[3].text, "This is A");
+    assert_eq!(tokens[1].span.line, 1);
+    assert_eq!(tokens[1].span.column, 7);
+}
+
+#[test]
+fn tokenize_braces_in_comments_and_strings_test() {
+    let tokens = tokenize("/* } */ char s[] = \"}\"; // }\n'}'");
+    assert!(!tokens.iter().any(|t| t.is_punct("}")));
+    assert_eq!(tokens.last().unwrap().kind, TokenKind::Char);
+}
+
+#[test]
+fn tokenize_preprocessor_test() {
+    let tokens = tokenize("#define more_stuff \\\n 25125125 //!< satasjt\nint a;");
+    assert_eq!(tokens[0].kind, TokenKind::Preprocessor);
+    assert_eq!(tokens[0].text, "#define more_stuff   25125125");
+    assert_eq!(tokens[1].kind, TokenKind::Comment(CommentKind::DocTrailing));
+    assert_eq!(tokens[1].text, "satasjt");
+    assert_eq!(tokens[2].span.line, 3);
+}
+
+#[test]
+fn tokenize_operators_test() {
+    let tokens = tokenize("1u << 3 | 0x1e-2 >>= 1.5e-3");
+    let text = tokens.iter().map(|t| t.text.as_str()).collect::<Vec<_>>();
+    assert_eq!(
+        text,
+        vec!["1u", "<<", "3", "|", "0x1e", "-", "2", ">>=", "1.5e-3"]
+    );
+}
+
+#[test]
+fn tokenize_cyrillic_test() {
+    let tokens = tokenize("   TEST, //!< описание");
+    assert_eq!(tokens[2].text, "описание");
+    assert_eq!(tokens[2].span.column, 10);
+}
+
+#[test]
+fn tokenize_doc_comment_forms_test() {
+    let tokens = tokenize(
+        "/** Javadoc */ /*! Qt */ /// Slashes\n\n//! Bang\n/**< After */ /*!< After */ ///< After\n//!< After",
+    );
+    let kinds = tokens.iter().map(|t| t.kind.clone()).collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        vec![
+            TokenKind::Comment(CommentKind::Doc),
+            TokenKind::Comment(CommentKind::Doc),
+            TokenKind::Comment(CommentKind::Doc),
+            TokenKind::Comment(CommentKind::Doc),
+            TokenKind::Comment(CommentKind::DocTrailing),
+            TokenKind::Comment(CommentKind::DocTrailing),
+            TokenKind::Comment(CommentKind::DocTrailing),
+        ]
+    );
+    assert!(tokens[..6]
+        .iter()
+        .all(|t| t.text.split_whitespace().count() == 1));
+    //The `//!<` comment on the next line continues the `///<` one
+    assert_eq!(tokens[6].text, "After\nAfter");
+}
+
+#[test]
+fn tokenize_not_doc_comments_test() {
+    let tokens = tokenize("//// Line\n/*****/\n/**/\n/********** Banner */\n// Comment");
+    assert!(tokens
+        .iter()
+        .all(|t| t.kind == TokenKind::Comment(CommentKind::Plain)));
+}
+
+#[test]
+fn tokenize_multiline_doc_comment_test() {
+    let tokens =
+        tokenize("/**\n * First line\n * Second line\n */\n/// Third\n/// Fourth\n\n/// Fifth");
+    assert_eq!(tokens.len(), 3);
+    assert_eq!(tokens[0].text, "First line\nSecond line");
+    assert_eq!(tokens[1].text, "Third\nFourth");
+    assert_eq!(tokens[1].span.line, 5);
+    assert_eq!(tokens[2].text, "Fifth");
+}
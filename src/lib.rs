@@ -0,0 +1,66 @@
+//The GPLv3 License (GPLv3)
+//
+//Copyright (c) 2023 Ciubix8513
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//Everything the binaries do, for calling the parser and the exporter from other Rust code:
+//
+//let data = doxygen_gen::parse_file(PathBuf::from("a.h"), doxygen_gen::AUTO)?;
+//doxygen_gen::export_doc(data, PathBuf::from("a.docx"), &ExportConfig::default())?;
+//
+//The modules that aren't public are implementation details of the parser
+
+//Module to define all the needed regex
+mod regex;
+//C tokenizer module
+mod lexer;
+//Parser warnings and errors module
+pub mod diagnostics;
+//Encoding detection module
+pub mod charset;
+//Constant expression evaluator module
+mod eval;
+//Conditional compilation module
+mod preprocessor;
+//Doxygen comment commands module
+pub mod doc_comment;
+//Field annotation module
+pub mod annotation;
+//Doxygen groups module
+pub mod groups;
+//Config file module
+pub mod config;
+//Struct layout module
+pub mod layout;
+//Header parser module
+pub mod parser;
+//Doxygen XML frontend module
+pub mod doxygen_xml;
+//Cross-file type lookup module
+pub mod symbols;
+//Data exporter module
+pub mod exporter;
+
+pub use charset::AUTO;
+pub use config::{Config, ExportConfig};
+pub use diagnostics::{Diagnostic, Severity};
+pub use doxygen_xml::parse_doxygen_xml;
+pub use exporter::export_doc;
+pub use parser::{
+    parse_file, parse_file_with_config, parse_reader, parse_reader_with_config, parse_source,
+    parse_source_with_config, parse_str, parse_str_with_config, DocumentationData,
+    DocumentationItem, DocumentationItemChild, DocumentationType,
+};
+pub use symbols::SymbolTable;
@@ -15,22 +15,16 @@
 //You should have received a copy of the GNU General Public License
 //along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-#![allow(dead_code)]
-use std::{path::PathBuf, sync::{mpsc, Arc, Mutex}};
+use std::{io, path::PathBuf, sync::{mpsc, Arc, Mutex}};
 
-use clap::{self, command, Parser};
+use clap::{self, Parser};
 use helpers::process_file;
+use doxygen_gen::{charset, parse_doxygen_xml, parse_reader_with_config, Config, SymbolTable};
 use indicatif::{ProgressBar, ProgressStyle};
 use rayon::{prelude::*, ThreadPoolBuilder};
 
 //Modele for some helper funcs
 mod helpers;
-//Module to define all the needed regex
-mod regex;
-//Header parser module
-mod parser;
-//Data exporter module
-mod exporter;
 
 #[derive(Parser, Debug)]
 #[command(
@@ -43,14 +37,22 @@ mod exporter;
      doxygen_gen-cli --files \"a.h b.h c.h\" --output out\n\
      doxygen_gen-cli --files \"a.h b.h\" --file c.h --output out\n\
      doxygen_gen-cli --files \"a.h b.h c.h\" --output out --encoding windows-1251\n\
-     doxygen_gen-cli --file a.h --file b.h --file c.h -output out"
+     doxygen_gen-cli --file a.h --file b.h --file c.h -output out\n\
+     doxygen_gen-cli --file a.h --output out --config doxygen_gen.toml\n\
+     doxygen_gen-cli --file a.h --output out -D VARIANT=2 -D LEGACY_PROTOCOL -U DEBUG\n\
+     doxygen_gen-cli --file a.h --output out -I include -I ../common\n\
+     doxygen_gen-cli --file a.h --output out --locations\n\
+     doxygen_gen-cli --file a.h --output out --encoding windows-1251 --strict\n\
+     doxygen_gen-cli --file a.h --output out --abi arm-eabi\n\
+     git show HEAD:src/a.h | doxygen_gen-cli --stdin src/a.h --output out\n\
+     doxygen_gen-cli --doxygen-xml build/doxygen/xml --output out"
 )]
 struct Args {
     #[arg(
         short,
         long,
         help = "Specifies file to process, can be used multiple times",
-        required_unless_present("files")
+        required_unless_present_any(["files", "stdin", "doxygen_xml"])
     )]
     file: Vec<String>,
     #[arg(short, long, help = "Specifies output directory")]
@@ -58,17 +60,69 @@ struct Args {
     #[arg(
         short,
         long,
-        help = "Specifies encoding of the files",
-        default_value = "utf-8"
+        help = "Specifies encoding of the files, auto detects it for each file",
+        default_value = "auto"
     )]
     encoding: String,
     #[arg(
         short = 'F',
         long,
         help = "Speciefies files to process, a string of files",
-        required_unless_present("file")
+        required_unless_present_any(["file", "stdin", "doxygen_xml"])
     )]
     files: Option<String>,
+    #[arg(
+        short,
+        long,
+        help = "Specifies a toml file with the annotation keys"
+    )]
+    config: Option<String>,
+    #[arg(
+        short = 'D',
+        long,
+        help = "Defines a macro for #if/#ifdef, NAME or NAME=VALUE, can be used multiple times"
+    )]
+    define: Vec<String>,
+    #[arg(
+        short = 'U',
+        long,
+        help = "Undefines a macro, can be used multiple times"
+    )]
+    undefine: Vec<String>,
+    #[arg(
+        short = 'I',
+        long,
+        help = "Adds a directory to search the included headers in, can be used multiple times"
+    )]
+    include: Vec<String>,
+    #[arg(
+        short = 'L',
+        long,
+        help = "Adds the header lines every table and row come from"
+    )]
+    locations: bool,
+    #[arg(
+        long,
+        help = "Fails the files with bytes the encoding can't decode instead of dropping the bytes"
+    )]
+    strict: bool,
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Reads a header from stdin, NAME is used in the messages, for the includes and for the output file"
+    )]
+    stdin: Option<String>,
+    #[arg(
+        long,
+        help = "Adds the field offsets, sizes and padding for a target: arm-eabi, x86-64-sysv or dsp16"
+    )]
+    abi: Option<String>,
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Reads the headers documented in doxygen's XML output, DIR is the one with index.xml"
+    )]
+    doxygen_xml: Option<String>,
 }
 
 fn main() {
@@ -86,17 +140,18 @@ fn main() {
        files.append(f.split(' ').map(str::to_string).collect::<Vec<String>>().as_mut());
     }
     //An iter of all files
-    let files = files.iter().map(PathBuf::from).collect::<Vec<PathBuf>>();
+    let mut files = files.iter().map(PathBuf::from).collect::<Vec<PathBuf>>();
     for f in files.iter(){
         if !f.is_file() && !f.exists(){
             println!("Invalid files {}",f.display());
             return
         }
     }
-    let num_files = files.len();
+    //The header from stdin doesn't have to exist, it's only a name
+    let stdin = args.stdin.map(PathBuf::from);
 
     let encoding = args.encoding.to_lowercase().replace(' ', "-");
-    let encoding = encoding::all::encodings().iter().find(|x| x.name() == encoding);
+    let encoding = charset::encodings().into_iter().find(|x| x.name() == encoding);
     if encoding.is_none(){
         println!("Invalid encoding");
         return
@@ -104,6 +159,55 @@ fn main() {
 
     let encoding = encoding.unwrap();
 
+    let mut config = match args.config.map(PathBuf::from) {
+        Some(path) => match Config::load(&path) {
+            Ok(config) => config,
+            Err(e) => {
+                println!("Invalid config {}: {}", path.display(), e);
+                return
+            }
+        },
+        None => Config::default(),
+    };
+    //The command line overrides the config
+    for define in &args.define {
+        config.preprocessor.define(define);
+    }
+    for undefine in &args.undefine {
+        config.preprocessor.undefine(undefine);
+    }
+    config.preprocessor.include_paths.extend(args.include.iter().map(PathBuf::from));
+    config.export.locations |= args.locations;
+    config.decoding.strict |= args.strict;
+    if args.abi.is_some() {
+        config.layout.abi = args.abi;
+    }
+    if let Err(e) = config.layout.abi() {
+        println!("{}", e);
+        return
+    }
+
+    //All the files have to be parsed before exporting, so that types can be looked up across them
+    let mut sources = Vec::new();
+    if let Some(name) = &stdin {
+        sources.push((name.clone(), parse_reader_with_config(io::stdin().lock(), name, encoding, &config)));
+    }
+    //The headers don't have to exist here either, doxygen already read them
+    if let Some(directory) = args.doxygen_xml.map(PathBuf::from) {
+        match parse_doxygen_xml(&directory, &config) {
+            Ok(headers) => sources.extend(headers.into_iter().map(|(name, data)| (name, Ok(data)))),
+            Err(e) => {
+                println!("Invalid doxygen XML {}: {}", directory.display(), e);
+                return
+            }
+        }
+    }
+    //Exported like the rest of the files
+    let names = sources.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>();
+    let symbols = SymbolTable::load_with(&files, sources, encoding, &config);
+    files.extend(names);
+    let num_files = files.len();
+
     let bar = ProgressBar::new(num_files as u64)
         .with_message(format!("Processing {} files",num_files))
         .with_style(ProgressStyle::default_bar()
@@ -117,7 +221,7 @@ fn main() {
 
     pool.spawn(move||{
         files.par_iter().for_each(|f|{
-            process_file(f.clone(), &output, encoding.to_owned());
+            process_file(f.clone(), &output, &symbols, &config.export);
             tx.lock().unwrap().send(()).unwrap();
         });
     });
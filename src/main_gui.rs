@@ -19,12 +19,6 @@ use iced::Application;
 use iced::Settings;
 //Modele for some helper funcs
 mod helpers;
-//Module to define all the needed regex
-mod regex;
-//Header parser module
-mod parser;
-//Data exporter module
-mod exporter;
 //module for the iced window
 mod main_window;
 
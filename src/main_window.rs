@@ -19,7 +19,7 @@ use iced::{
     executor,
     futures::channel::mpsc::{channel, Receiver, Sender},
     subscription,
-    widget::{button, column, container, pick_list, progress_bar, row, scrollable, text},
+    widget::{button, checkbox, column, container, pick_list, progress_bar, row, scrollable, text},
     Alignment, Application, Command, Length, Subscription, Theme,
 };
 use once_cell::sync::Lazy;
@@ -32,9 +32,12 @@ use std::{
     sync::{Arc, Mutex},
 };
 use crate::helpers::process_file;
+use doxygen_gen::{charset, Config, SymbolTable};
 
+#[allow(clippy::type_complexity)]
 static CHANEL_SENDER: Lazy<Arc<Mutex<Option<Sender<Option<PathBuf>>>>>> =
     Lazy::new(|| Arc::new(Mutex::new(None)));
+#[allow(clippy::type_complexity)]
 static CHANEL_RECEIVER: Lazy<Arc<Mutex<Option<Receiver<Option<PathBuf>>>>>> =
     Lazy::new(|| Arc::new(Mutex::new(None)));
 
@@ -44,6 +47,12 @@ pub struct MainWindow {
     output_directory: PathBuf,
     processing: bool,
     progress: (i32, i32),
+    //Add the source lines to the tables
+    locations: bool,
+    //Fail the files with undecodable bytes
+    strict: bool,
+    //The grammar, defines and ABI, doxygen_gen.toml from the working directory if there is one
+    config: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone)]
@@ -52,7 +61,10 @@ pub enum Message {
     PickList(String),
     ProccessButtonClick,
     SaveDirectoryButtonClick,
+    ConfigButtonClick,
     ProgressChanged((PathBuf, bool)),
+    LocationsToggled(bool),
+    StrictToggled(bool),
 }
 
 impl Application for MainWindow {
@@ -66,10 +78,13 @@ impl Application for MainWindow {
         (
             MainWindow {
                 files: Vec::default(),
-                encoding: Some("utf-8".to_owned()),
+                encoding: Some("auto".to_owned()),
                 output_directory: PathBuf::default(),
                 processing: false,
                 progress: (0, 0),
+                locations: false,
+                strict: false,
+                config: Some(PathBuf::from("doxygen_gen.toml")).filter(|path| path.exists()),
             },
             Command::none(),
         )
@@ -96,15 +111,39 @@ impl Application for MainWindow {
                     self.output_directory = dir;
                 }
             }
+            Message::ConfigButtonClick => {
+                let config = FileDialog::new()
+                    .add_filter("Config", &["toml"])
+                    .pick_file();
+                if config.is_some() {
+                    self.config = config;
+                }
+            }
             Message::ProccessButtonClick => {
-                let encoding = encoding::all::encodings()
-                    .iter()
+                let encoding = charset::encodings()
+                    .into_iter()
                     .find(|x| x.name() == self.encoding.clone().unwrap())
                     .unwrap();
 
                 //Prepare data for multithreading
                 let files = self.files.clone();
                 let output_directory = self.output_directory.clone();
+                let mut config = match &self.config {
+                    Some(path) => match Config::load(path) {
+                        Ok(config) => config,
+                        Err(e) => {
+                            println!("Invalid config {}: {}", path.display(), e);
+                            return Command::none();
+                        }
+                    },
+                    None => Config::default(),
+                };
+                config.export.locations |= self.locations;
+                config.decoding.strict |= self.strict;
+                if let Err(e) = config.layout.abi() {
+                    println!("{}", e);
+                    return Command::none();
+                }
                 let pool = rayon::ThreadPoolBuilder::new()
                     //use max num of threads (Add config for that?)
                     .num_threads(0)
@@ -113,8 +152,9 @@ impl Application for MainWindow {
                 self.processing = true;
                 self.progress = (0, self.files.len() as i32);
                 pool.spawn(move || {
+                    let symbols = SymbolTable::load(&files, encoding, &config);
                     files.par_iter().for_each(|file| {
-                        process_file(file.clone(), &output_directory, encoding.to_owned());
+                        process_file(file.clone(), &output_directory, &symbols, &config.export);
                         let _ = CHANEL_SENDER
                             .lock()
                             .unwrap()
@@ -131,6 +171,8 @@ impl Application for MainWindow {
                 });
             }
             Message::PickList(e) => self.encoding = Some(e),
+            Message::LocationsToggled(locations) => self.locations = locations,
+            Message::StrictToggled(strict) => self.strict = strict,
             Message::ProgressChanged((item, finished)) => {
                 if finished {
                     self.processing = false;
@@ -150,21 +192,22 @@ impl Application for MainWindow {
         let mut go_button = button("Process");
         let mut open_button = button("Select files");
         let mut save_dir_button = button("Save directory");
+        let mut config_button = button("Config file");
 
         //Disable the buttons when needed
         if !self.processing {
             open_button = open_button.on_press(Message::OpenFileButtonClick);
             save_dir_button = save_dir_button.on_press(Message::SaveDirectoryButtonClick);
+            config_button = config_button.on_press(Message::ConfigButtonClick);
             if !self.files.is_empty() && self.output_directory.exists() {
                 go_button = go_button.on_press(Message::ProccessButtonClick)
             }
         }
 
         let encodings_list = pick_list(
-            encoding::all::encodings()
+            charset::encodings()
                 .iter()
                 .map(|e| e.name().to_owned())
-                .filter(|x| x != "error")
                 .collect::<Cow<'_, _>>(),
             self.encoding.clone(),
             Message::PickList,
@@ -186,6 +229,13 @@ impl Application for MainWindow {
             .align_items(Alignment::Center);
 
         let save_dit_text = text(self.output_directory.display()).width(180);
+        let locations = checkbox("Source lines", self.locations, Message::LocationsToggled);
+        let strict = checkbox("Strict decoding", self.strict, Message::StrictToggled);
+        let config_text = match &self.config {
+            Some(config) => text(config.file_name().unwrap_or_default().to_string_lossy()),
+            None => text("No config"),
+        }
+        .width(180);
         //Add in the progress bar if processing 
         let save_column = if self.processing {
             let progress = row![
@@ -201,6 +251,10 @@ impl Application for MainWindow {
             column![
                 text("Encoding:"),
                 encodings_list,
+                locations,
+                strict,
+                config_button,
+                config_text,
                 save_dir_button,
                 save_dit_text,
                 go_button,
@@ -213,6 +267,10 @@ impl Application for MainWindow {
             column![
                 text("Encoding:"),
                 encodings_list,
+                locations,
+                strict,
+                config_button,
+                config_text,
                 save_dir_button,
                 save_dit_text,
                 go_button
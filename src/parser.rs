@@ -15,145 +15,1658 @@
 //You should have received a copy of the GNU General Public License
 //along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use crate::regex::*;
-use encoding::{DecoderTrap, Encoding};
-use std::{fs::OpenOptions, io::Read, path::PathBuf};
-
-enum ParsingState {
-    None,
-    Name,
-    Fields,
-}
+use crate::{
+    annotation::{Annotation, Column},
+    charset::{decode, detect, AUTO},
+    config::Config,
+    diagnostics::{Diagnostic, Severity},
+    doc_comment::DocComment,
+    eval::Constants,
+    groups::Group,
+    layout::FieldLayout,
+    lexer::{tokenize, CommentKind, Span, Token, TokenKind},
+    preprocessor::{directive, preprocess},
+    regex::*,
+};
+use encoding::Encoding;
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::OpenOptions,
+    io::Read,
+    path::{Path, PathBuf},
+};
 
 #[derive(Default, Debug, Clone, PartialEq, Eq)]
 pub enum DocumentationType {
     #[default]
     Struct,
     Enum,
+    Union,
+    //`#define NAME value`
+    Define,
+    //`ret name(params);`
+    Function,
+}
+
+impl DocumentationType {
+    //The C keyword used to declare the type
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            DocumentationType::Struct => "struct",
+            DocumentationType::Enum => "enum",
+            DocumentationType::Union => "union",
+            DocumentationType::Define => "#define",
+            DocumentationType::Function => "function",
+        }
+    }
 }
 
 #[derive(Default, Debug, Clone)]
 pub struct DocumentationItemChild {
+    //The declared field or enumerator name, empty for anonymous members
+    pub name: String,
+    //The raw declaration as written, `unsigned int flags : 3;`
     pub datatype: String,
+    //`unsigned int`, `struct header_s`, `uint8_t`
+    pub base_type: String,
+    //`const`, `volatile`, ... of the base type
+    pub qualifiers: Vec<String>,
+    //The number of `*` in the declarator
+    pub pointer_depth: usize,
+    //The qualifiers after each `*`, `int *const *p` is [["const"], []]
+    pub pointer_qualifiers: Vec<Vec<String>>,
+    //The parameter list of a function pointer, `int, char *`, `base_type` is its return type
+    pub function: Option<String>,
+    //`[4][N]` is ["4", "N"]
+    pub array_dims: Vec<String>,
+    //`: 3`
+    pub bit_width: Option<String>,
+    //`= 1 << 3` of an enumerator
+    pub value_expression: Option<String>,
+    //The value of an enumerator, None if it couldn't be computed
+    pub value: Option<i64>,
     pub code: String,
     pub note: String,
     pub additional_data: String,
     pub signed: String,
     pub bits: String,
+    //The scale as written, `annotation` knows whether it's the LSB or the MSB
     pub msb: String,
+    //The `key:value` pairs of the annotation, `signed`, `bits` and `msb` are their columns as text
+    pub annotation: Annotation,
+    //The whole comment, `note` is its brief description
+    pub doc: DocComment,
+    //The struct/union declared inline by this field
+    pub nested: Option<Box<DocumentationItem>>,
+    //Where the type of the field is documented, filled in by the symbol table
+    pub type_ref: Option<TypeRef>,
+    pub location: Location,
+    //The offset, size and padding, filled in by the symbol table if there is a target ABI
+    pub layout: Option<FieldLayout>,
+}
+
+//Where an item or a field is declared, comments aren't included
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    //Empty when the source didn't come from a file
+    pub file: PathBuf,
+    //1-based, like the spans
+    pub start_line: usize,
+    pub end_line: usize,
+    //Byte offsets into the decoded source
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Location {
+    //From the first to the last token that isn't a comment
+    fn of(tokens: &[Token], source: &str) -> Location {
+        let mut code = tokens.iter().filter(|t| !t.is_comment());
+        let (first, last) = match code.next() {
+            Some(first) => (first, code.next_back().unwrap_or(first)),
+            None => return Location::default(),
+        };
+        Location {
+            file: PathBuf::new(),
+            start_line: first.span.line,
+            //The last token itself can be split over lines, like a `#define`
+            end_line: first.span.line
+                + source[first.span.start..last.span.end]
+                    .matches('\n')
+                    .count(),
+            start: first.span.start,
+            end: last.span.end,
+        }
+    }
+
+    //`12` or `12-15`
+    pub fn lines(&self) -> String {
+        match self.start_line == self.end_line {
+            true => self.start_line.to_string(),
+            false => format!("{}-{}", self.start_line, self.end_line),
+        }
+    }
+}
+
+//`a.h:12-15`, only the name of the file to keep it short
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.file.file_name() {
+            Some(name) => write!(f, "{}:{}", name.to_string_lossy(), self.lines()),
+            None => write!(f, "{}", self.lines()),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct TypeRef {
+    //The name of the documented item
+    pub name: String,
+    pub r#type: DocumentationType,
+    //The header it is declared in
+    pub file: PathBuf,
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct FunctionParameter {
+    //The whole declaration, `const char *name`
+    pub datatype: String,
+    //Empty for unnamed parameters
+    pub name: String,
+    //From `@param[in]`, empty if it wasn't specified
+    pub direction: String,
+    pub note: String,
+}
+
+impl DocumentationItemChild {
+    //The declaration as written, without the terminator
+    pub fn declaration(&self) -> String {
+        let declaration = self.datatype.trim_end();
+        declaration
+            .strip_suffix([';', ','])
+            .unwrap_or(declaration)
+            .trim_end()
+            .to_owned()
+    }
+
+    //Same as `declaration`, but with the name replaced, e.g. by a `parent.field` path
+    pub fn declaration_named(&self, name: &str) -> String {
+        let mut tokens = tokenize(&self.declaration());
+        match declared_name_index(&tokens) {
+            Some(i) if !self.name.is_empty() && tokens[i].text == self.name => {
+                tokens[i].text = name.to_owned();
+                join_tokens(&tokens)
+            }
+            _ => self.declaration(),
+        }
+    }
 }
 
 #[derive(Default, Debug, Clone)]
 pub struct DocumentationItem {
     pub r#type: DocumentationType,
+    //What the caption calls the item, `doc.summary()`, defines have the whole brief in their row instead
     pub note: String,
+    pub doc: DocComment,
+    //The name the code refers to the type by, the typedef alias if there is one, otherwise the tag
     pub name: String,
+    //`struct tag {}`
+    pub tag: Option<String>,
+    //`typedef struct {} alias;`
+    pub alias: Option<String>,
+    //The replacement text of a `#define`
+    pub value: String,
+    //The return type of a function
+    pub return_type: String,
+    pub parameters: Vec<FunctionParameter>,
     pub children: Vec<DocumentationItemChild>,
+    //Members without documentation, with the index of the child they come before, they still take space
+    pub undocumented: Vec<(usize, DocumentationItemChild)>,
+    pub location: Location,
+    //The maximum alignment from `#pragma pack` or `__attribute__((packed))`
+    pub pack: Option<usize>,
+    //`sizeof` and `_Alignof` in bytes, filled in together with the layout of the children
+    pub size: Option<usize>,
+    pub align: Option<usize>,
+    //The name of the `@defgroup` the item is in, from `@ingroup` or a `@{` block
+    pub group: Option<String>,
 }
 
 #[derive(Default, Debug, Clone)]
 pub struct DocumentationData {
-    //Potentially add other data here
     pub items: Vec<DocumentationItem>,
+    //The groups defined or added to in the file, the items refer to them by name
+    pub groups: Vec<Group>,
+    //The headers included by the active code, as written, `"a.h"` or `<a.h>`
+    pub includes: Vec<String>,
+    //Everything the parser had to skip or guess, in the order it was found
+    pub diagnostics: Vec<Diagnostic>,
+    //The encoding `auto` picked for the file, None if it was chosen by the user
+    pub encoding: Option<String>,
+    //`typedef unsigned short word_t;`, the declarations of the simple typedefs by their names
+    pub typedefs: HashMap<String, DocumentationItemChild>,
+    //The structs, unions and enums without documentation by their tags and aliases, they still take space in the documented ones
+    pub undocumented: HashMap<String, DocumentationItem>,
+    //Every object-like macro and enumerator of the file, for evaluating array lengths
+    pub macros: HashMap<String, String>,
+    pub enumerators: HashMap<String, i64>,
+}
+
+impl DocumentationData {
+    //Everything parsed from a source knows where it is, but not which file it is
+    pub fn set_file(&mut self, file: &Path) {
+        for diagnostic in self.diagnostics.iter_mut() {
+            diagnostic.file = file.to_owned();
+        }
+        for group in self.groups.iter_mut() {
+            group.location.file = file.to_owned();
+        }
+        set_file(&mut self.items, file);
+    }
+}
+
+fn set_file(items: &mut [DocumentationItem], file: &Path) {
+    for item in items {
+        item.location.file = file.to_owned();
+        for child in item.children.iter_mut() {
+            child.location.file = file.to_owned();
+            if let Some(nested) = child.nested.as_mut() {
+                set_file(std::slice::from_mut(nested.as_mut()), file);
+            }
+        }
+    }
 }
 
 pub fn parse_file(
     path: PathBuf,
     encoding: &dyn Encoding,
 ) -> Result<DocumentationData, std::io::Error> {
-    let mut file = OpenOptions::new().read(true).open(path)?;
+    parse_file_with_config(path, encoding, &Config::default())
+}
+
+pub fn parse_file_with_config(
+    path: PathBuf,
+    encoding: &dyn Encoding,
+    config: &Config,
+) -> Result<DocumentationData, std::io::Error> {
+    let file = OpenOptions::new().read(true).open(&path)?;
+    parse_reader_with_config(file, &path, encoding, config)
+}
+
+//`name` is what the diagnostics and the locations refer to, e.g. `HEAD:src/a.h` or `<stdin>`
+pub fn parse_reader(
+    reader: impl Read,
+    name: &Path,
+    encoding: &dyn Encoding,
+) -> Result<DocumentationData, std::io::Error> {
+    parse_reader_with_config(reader, name, encoding, &Config::default())
+}
+
+pub fn parse_reader_with_config(
+    mut reader: impl Read,
+    name: &Path,
+    encoding: &dyn Encoding,
+    config: &Config,
+) -> Result<DocumentationData, std::io::Error> {
     let contents = &mut Vec::<u8>::new();
-    file.read_to_end(contents)?;
+    reader.read_to_end(contents)?;
 
-    //Decode the file before processing it
-    let contents = encoding.decode(contents, DecoderTrap::Ignore).unwrap();
+    //Decode the file before processing it, `auto` is resolved here to know what it picked
+    let detected = (encoding.name() == AUTO.name()).then(|| detect(contents));
+    let (decoder, bom): (&dyn Encoding, usize) = match detected {
+        Some((encoding, bom)) => (encoding, bom),
+        None => (encoding, 0),
+    };
+    let (contents, invalid) = decode(contents, bom, decoder);
+    if config.decoding.strict && !invalid.is_empty() {
+        let invalid = invalid.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("invalid {} bytes {}", decoder.name(), invalid.join(", ")),
+        ));
+    }
 
-    let mut data = DocumentationData::default();
-    //Have to asign the default value, even tho it's not used
-    let mut curret_item = DocumentationItem::default();
-    let mut parsing_state = ParsingState::None;
+    let mut data = parse_source_with_config(&contents, config);
+    for invalid in invalid {
+        data.diagnostics.push(Diagnostic {
+            severity: Severity::Warning,
+            message: format!(
+                "Invalid {} bytes `{}` at byte {} are dropped",
+                decoder.name(),
+                invalid.hex(),
+                invalid.offset
+            ),
+            file: PathBuf::new(),
+            line: invalid.line,
+            column: invalid.column,
+        });
+    }
+    data.diagnostics.sort_by_key(|d| (d.line, d.column));
+    data.set_file(name);
+    data.encoding = detected.map(|(encoding, _)| encoding.name().to_owned());
+    Ok(data)
+}
 
-    //Precompile the regex objects
-    let def_regex = name_regex();
-    let field_regex = field_regex();
-    let field_code_regex = field_code_regex();
-    let additional_data_regex = additional_data_regex();
-    let signed_regex = signed_data_regex();
+//Already decoded text, `name` is the same as for `parse_reader`
+pub fn parse_str(source: &str, name: &Path) -> DocumentationData {
+    parse_str_with_config(source, name, &Config::default())
+}
 
-    for line in contents.lines() {
-        if line.is_empty() {
-            continue;
+pub fn parse_str_with_config(source: &str, name: &Path, config: &Config) -> DocumentationData {
+    let mut data = parse_source_with_config(source, config);
+    data.set_file(name);
+    data
+}
+
+pub fn parse_source(source: &str) -> DocumentationData {
+    parse_source_with_config(source, &Config::default())
+}
+
+pub fn parse_source_with_config(source: &str, config: &Config) -> DocumentationData {
+    //Only the variant selected by the defines gets documented
+    let defines = config.preprocessor.macros();
+    let mut diagnostics = Vec::new();
+    let tokens = preprocess(tokenize(source), &defines, &mut diagnostics);
+    let mut parser = Parser::new(config, source, &tokens);
+    parser.data.diagnostics = diagnostics;
+    parser.parse();
+    //Undocumented members are only reported once their item is done
+    parser.data.diagnostics.sort_by_key(|d| (d.line, d.column));
+    parser.finish()
+}
+
+//The members of a struct/enum/union body
+#[derive(Default)]
+struct Members {
+    children: Vec<DocumentationItemChild>,
+    //With the index of the child they come before
+    undocumented: Vec<(usize, DocumentationItemChild)>,
+    //Whether the closing brace was found
+    closed: bool,
+}
+
+pub(crate) struct Parser<'a> {
+    config: &'a Config,
+    //The decoded source the spans point into
+    source: &'a str,
+    tokens: &'a [Token],
+    pos: usize,
+    //Doc comment waiting for the declaration it documents
+    pending_doc: Option<String>,
+    data: DocumentationData,
+    //Enumerators and macros seen so far, enum values may refer to them
+    constants: Constants,
+    //The value of the next enumerator without an explicit value
+    next_enumerator: Option<i64>,
+    //Warnings about undocumented members, only reported if the item itself is documented
+    undocumented: Vec<Diagnostic>,
+    //`#pragma pack(N)`, None is the default packing
+    pack: Option<usize>,
+    //`#pragma pack(push)`
+    pack_stack: Vec<Option<usize>>,
+    //The groups of the open `@{` blocks, a block without a group of its own is in the one it's nested in
+    group_stack: Vec<Option<String>>,
+    field_code_regex: Regex,
+    additional_data_regex: Regex,
+}
+
+impl<'a> Parser<'a> {
+    pub(crate) fn new(config: &'a Config, source: &'a str, tokens: &'a [Token]) -> Parser<'a> {
+        Parser {
+            config,
+            source,
+            tokens,
+            pos: 0,
+            pending_doc: None,
+            data: DocumentationData::default(),
+            undocumented: Vec::new(),
+            pack: None,
+            pack_stack: Vec::new(),
+            group_stack: Vec::new(),
+            constants: Constants {
+                macros: config.preprocessor.macros(),
+                ..Default::default()
+            },
+            next_enumerator: Some(0),
+            //Precompile the regex objects
+            field_code_regex: field_code_regex(),
+            additional_data_regex: additional_data_regex(),
         }
-        match parsing_state {
-            ParsingState::None => {
-                //Encountered a struct/enum definition
-                if line.contains("//! ") {
-                    curret_item = DocumentationItem {
-                        note: line[4..].to_owned(),
-                        ..Default::default()
-                    };
-                    parsing_state = ParsingState::Name;
+    }
+
+    pub(crate) fn finish(mut self) -> DocumentationData {
+        //Array lengths may need them after parsing
+        self.data.macros = self.constants.macros;
+        self.data.enumerators = self.constants.values;
+        self.data
+    }
+
+    //A field or an enumerator declared as `uint8_t data[8]` or `VALUE = 5`,
+    //for the frontends that don't parse the source themselves
+    pub(crate) fn member_from(
+        &mut self,
+        declaration: &str,
+        comment: &str,
+        r#type: &DocumentationType,
+    ) -> DocumentationItemChild {
+        let tokens = tokenize(declaration);
+        let mut child = self.parse_note(comment);
+        child.datatype = join_tokens(&tokens);
+        split_declaration(&tokens, r#type, &mut child);
+        if *r#type == DocumentationType::Enum {
+            child.value = self.enumerator_value(&child);
+        }
+        child
+    }
+
+    //`int send(const reply_t *reply)`, None if it isn't a prototype
+    pub(crate) fn function_from(
+        &self,
+        prototype: &str,
+        comment: &str,
+    ) -> Option<DocumentationItem> {
+        let mut item = parse_prototype(&tokenize(prototype))?;
+        document_function(&mut item, DocComment::parse(comment));
+        Some(item)
+    }
+
+    //Array lengths and enumerators may refer to it
+    pub(crate) fn define_from(&mut self, name: &str, value: &str) {
+        self.constants
+            .macros
+            .insert(name.to_owned(), value.trim().to_owned());
+    }
+
+    //The next enumerator without a value is 0 again
+    pub(crate) fn enum_from(&mut self) {
+        self.next_enumerator = Some(0);
+    }
+
+    fn token(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos)
+    }
+
+    //Skips over comments and returns the index of the next real token
+    fn next_significant(&self) -> usize {
+        let mut pos = self.pos;
+        while self.tokens.get(pos).is_some_and(Token::is_comment) {
+            pos += 1;
+        }
+        pos
+    }
+
+    fn significant(&mut self) -> Option<&'a Token> {
+        self.pos = self.next_significant();
+        self.tokens.get(self.pos)
+    }
+
+    fn parse(&mut self) {
+        while let Some(token) = self.token() {
+            let items = self.data.items.len();
+            //Only the blocks right above each other and the declaration are about it
+            if self.blank_line_before(self.pos) {
+                self.pending_doc = None;
+            }
+            match token.kind {
+                TokenKind::Comment(CommentKind::Doc) => {
+                    //Group and file comments aren't about the next declaration, the rest of the blocks are collected
+                    if !self.group_comment(token)
+                        && DocComment::parse(&token.text).tag("file").is_none()
+                    {
+                        self.pending_doc = Some(match self.pending_doc.take() {
+                            Some(doc) => format!("{}\n\n{}", doc, token.text),
+                            None => token.text.clone(),
+                        });
+                    }
+                    self.pos += 1;
+                }
+                //`//@{` works too
+                TokenKind::Comment(_) => {
+                    if is_group_marker(token.text.trim()) {
+                        self.group_comment(token);
+                    }
+                    self.pos += 1;
+                }
+                TokenKind::Preprocessor => {
+                    let doc = self.pending_doc.take();
+                    self.pos += 1;
+                    match directive(&token.text) {
+                        Some(("define", define)) => self.define(define, doc),
+                        Some(("undef", name)) => {
+                            self.constants.macros.remove(name.trim());
+                        }
+                        Some(("include", header)) => {
+                            self.data.includes.push(header.trim().to_owned())
+                        }
+                        Some(("pragma", pragma)) => self.pragma(pragma),
+                        _ => {}
+                    }
+                }
+                //Stray closing braces, e.g. from an `extern "C" {` block
+                TokenKind::Punct if token.is_punct("}") || token.is_punct(";") => self.pos += 1,
+                TokenKind::Identifier if token.text == "extern" => {
+                    let is_block = matches!(self.tokens.get(self.pos + 1), Some(t) if t.kind == TokenKind::String)
+                        && matches!(self.tokens.get(self.pos + 2), Some(t) if t.is_punct("{"));
+                    if is_block {
+                        self.pos += 3;
+                    } else {
+                        let doc = self.pending_doc.take();
+                        self.declaration(doc);
+                    }
+                }
+                TokenKind::Identifier if token.text == "typedef" => {
+                    let doc = self.pending_doc.take();
+                    let start = self.pos;
+                    self.pos += 1;
+                    if matches!(self.significant(), Some(t) if aggregate_type(t).is_some()) {
+                        self.aggregate(doc, Some(start));
+                    } else {
+                        self.typedef();
+                    }
+                }
+                TokenKind::Identifier if aggregate_type(token).is_some() => {
+                    let doc = self.pending_doc.take();
+                    self.aggregate(doc, None);
+                }
+                _ => {
+                    let doc = self.pending_doc.take();
+                    self.declaration(doc);
                 }
             }
-            ParsingState::Name => {
-                let captures = def_regex.captures(line);
-                if let Some(captures) = captures {
-                    if captures.get(1).unwrap().as_str() == "enum" {
-                        curret_item.r#type = DocumentationType::Enum;
+            self.group_items(items);
+        }
+        if !self.group_stack.is_empty() {
+            self.data.diagnostics.push(Diagnostic::warning(
+                &self.tokens[self.tokens.len() - 1].span,
+                format!(
+                    "{} group block(s) opened with `@{{` are never closed",
+                    self.group_stack.len()
+                ),
+            ));
+        }
+    }
+
+    //Whether an empty line separates the token from the one before it
+    fn blank_line_before(&self, pos: usize) -> bool {
+        let (previous, token) = match (pos.checked_sub(1), self.tokens.get(pos)) {
+            (Some(previous), Some(token)) => (&self.tokens[previous], token),
+            _ => return false,
+        };
+        //Lines of skipped `#if` blocks or directives aren't empty
+        let gap = &self.source[previous.span.end..token.span.start.max(previous.span.end)];
+        let mut lines = gap.split('\n');
+        lines.next();
+        lines.next_back();
+        lines.any(|line| line.trim().is_empty())
+    }
+
+    //`@defgroup`, `@addtogroup`, `@{` and `@}`, returns whether the comment was about a group
+    fn group_comment(&mut self, comment: &Token) -> bool {
+        let mut opens = 0;
+        let mut closes = 0;
+        let text = comment
+            .text
+            .lines()
+            .map(|line| {
+                let words = line.split_whitespace().filter(|word| match *word {
+                    "@{" | "\\{" => {
+                        opens += 1;
+                        false
+                    }
+                    "@}" | "\\}" => {
+                        closes += 1;
+                        false
                     }
-                    curret_item.name = captures.get(2).unwrap().as_str().to_owned();
-                    parsing_state = ParsingState::Fields;
-                } else if line.contains("#define ") {
-                    parsing_state = ParsingState::None;
+                    _ => true,
+                });
+                words.collect::<Vec<_>>().join(" ")
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let doc = DocComment::parse(&text);
+        let group = match (doc.tag("defgroup"), doc.tag("addtogroup")) {
+            (Some(definition), _) => self.define_group(definition, &doc, true, comment),
+            (None, Some(definition)) => self.define_group(definition, &doc, false, comment),
+            (None, None) => None,
+        };
+        if group.is_none() && opens == 0 && closes == 0 {
+            return false;
+        }
+        for _ in 0..opens {
+            let group = group
+                .clone()
+                .or_else(|| self.group_stack.last().cloned().flatten());
+            self.group_stack.push(group);
+        }
+        for _ in 0..closes {
+            if self.group_stack.pop().is_none() {
+                self.data.diagnostics.push(Diagnostic::warning(
+                    &comment.span,
+                    "`@}` without a matching `@{` is ignored".to_owned(),
+                ));
+            }
+        }
+        true
+    }
+
+    //`name Title`, `@addtogroup` only sets the title if the group doesn't have one yet
+    fn define_group(
+        &mut self,
+        definition: &str,
+        doc: &DocComment,
+        define: bool,
+        comment: &Token,
+    ) -> Option<String> {
+        let (name, title) = definition.split_once(' ').unwrap_or((definition, ""));
+        if name.is_empty() {
+            return None;
+        }
+        let title = title.trim();
+        let parent = doc
+            .tag("ingroup")
+            .and_then(|groups| groups.split_whitespace().next())
+            .map(str::to_owned)
+            .or_else(|| self.group_stack.last().cloned().flatten())
+            .filter(|parent| parent != name);
+        match self.data.groups.iter_mut().find(|g| g.name == name) {
+            Some(group) => {
+                if !title.is_empty() && (define || group.title == group.name) {
+                    group.title = title.to_owned();
+                }
+                if define {
+                    group.doc = doc.clone();
+                    group.parent = parent.or(group.parent.take());
                 }
             }
-            ParsingState::Fields => {
-                let captures = field_regex.captures(line);
-                if let Some(captures) = captures {
-                    let mut note = captures.get(2).unwrap().as_str();
-                    let mut code = "-";
-                    let mut data = "-";
-                    let mut signed = "-";
-                    let mut bits = "-";
-                    let mut msb = "-";
-                    if note.contains(" //") {
-                        if let Some(captures) = additional_data_regex.captures(note) {
-                            note = captures.get(1).unwrap().as_str();
-                            data = captures.get(2).unwrap().as_str();
-                            if let Some(captures) = signed_regex.captures(data) {
-                                data = captures.get(1).unwrap().as_str();
-                                signed = captures.get(2).unwrap().as_str();
-                                bits = captures.get(3).unwrap().as_str();
-                                msb = captures.get(4).unwrap().as_str();
-                            }
-                        }
+            None => self.data.groups.push(Group {
+                name: name.to_owned(),
+                title: match title.is_empty() {
+                    true => name.to_owned(),
+                    false => title.to_owned(),
+                },
+                doc: doc.clone(),
+                parent,
+                location: Location::of(std::slice::from_ref(comment), self.source),
+            }),
+        }
+        Some(name.to_owned())
+    }
+
+    //The items declared since `from` go to their `@ingroup`, or to the group of the block they are in
+    fn group_items(&mut self, from: usize) {
+        let block = self.group_stack.last().cloned().flatten();
+        for item in self.data.items[from..].iter_mut() {
+            let ingroup = item
+                .doc
+                .tag("ingroup")
+                .and_then(|groups| groups.split_whitespace().next())
+                .map(str::to_owned);
+            item.group = ingroup.or_else(|| block.clone());
+        }
+    }
+
+    //Any other declaration, only function prototypes are documented
+    fn declaration(&mut self, doc: Option<String>) {
+        let start = self.pos;
+        let mut declaration = Vec::new();
+        let mut depth = 0usize;
+        let mut has_body = false;
+        while let Some(token) = self.token() {
+            if token.is_comment() {
+                self.pos += 1;
+                continue;
+            }
+            if token.kind == TokenKind::Preprocessor {
+                break;
+            }
+            if depth == 0 && (token.is_punct(";") || token.is_punct("{")) {
+                has_body = token.is_punct("{");
+                break;
+            }
+            if token.is_punct("(") || token.is_punct("[") {
+                depth += 1;
+            } else if token.is_punct(")") || token.is_punct("]") {
+                depth = depth.saturating_sub(1);
+            }
+            declaration.push(token.clone());
+            self.pos += 1;
+        }
+        //Not something we know how to document, let the generic code deal with it
+        let item = match parse_prototype(&declaration) {
+            Some(item) => item,
+            None => {
+                self.pos = start;
+                return self.skip_declaration();
+            }
+        };
+        if has_body {
+            self.skip_declaration();
+        } else if self.token().is_some_and(|t| t.is_punct(";")) {
+            self.pos += 1;
+        }
+        let location = Location::of(&self.tokens[start..self.pos], self.source);
+
+        let mut notes = doc.into_iter().collect::<Vec<_>>();
+        self.trailing_notes(&mut notes);
+        if notes.is_empty() {
+            return;
+        }
+        let mut item = item;
+        document_function(&mut item, DocComment::parse(&notes.join("\n\n")));
+        item.location = location;
+        self.data.items.push(item);
+    }
+
+    //Parses the `NAME value` part of a `#define`, the directive itself is already consumed
+    fn define(&mut self, define: &str, doc: Option<String>) {
+        let define = define.trim();
+        let name_end = define
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(define.len());
+        //Function-like macros keep their parameter list as a part of the name
+        let name_end = match define[name_end..].starts_with('(') {
+            true => define.find(')').map_or(define.len(), |e| e + 1),
+            false => name_end,
+        };
+        let (name, value) = define.split_at(name_end);
+        if !name.is_empty() && !name.ends_with(')') {
+            self.constants
+                .macros
+                .insert(name.to_owned(), value.trim().to_owned());
+        }
+
+        //The directive is the previous token
+        let location = Location::of(&self.tokens[self.pos - 1..self.pos], self.source);
+        let mut notes = doc.into_iter().collect::<Vec<_>>();
+        self.trailing_notes(&mut notes);
+        if name.is_empty() || notes.is_empty() {
+            return;
+        }
+        let doc = DocComment::parse(&notes.join("\n\n"));
+        self.data.items.push(DocumentationItem {
+            r#type: DocumentationType::Define,
+            note: doc.brief.clone(),
+            doc,
+            name: name.to_owned(),
+            value: value.trim().to_owned(),
+            location,
+            ..Default::default()
+        });
+    }
+
+    //`typedef unsigned short word_t;`, the `typedef` is already consumed
+    fn typedef(&mut self) {
+        let start = self.pos;
+        self.skip_declaration();
+        let declaration = self.tokens[start..self.pos]
+            .iter()
+            .filter(|t| !t.is_comment())
+            .cloned()
+            .collect::<Vec<_>>();
+        //`typedef __packed struct { ... } name;` and the like aren't understood
+        if declaration.iter().any(|t| t.is_punct("{")) {
+            return;
+        }
+        let mut child = DocumentationItemChild::default();
+        split_declaration(&declaration, &DocumentationType::Struct, &mut child);
+        if !child.name.is_empty() && !child.base_type.is_empty() {
+            self.data.typedefs.insert(child.name.clone(), child);
+        }
+    }
+
+    //Only `#pragma pack` matters, it changes the layout of the structs after it
+    fn pragma(&mut self, pragma: &str) {
+        let arguments = match pragma.trim().strip_prefix("pack") {
+            Some(arguments) => arguments.trim(),
+            None => return,
+        };
+        let arguments = arguments
+            .strip_prefix('(')
+            .and_then(|a| a.strip_suffix(')'))
+            .unwrap_or_default();
+        let arguments = arguments
+            .split(',')
+            .map(str::trim)
+            .filter(|a| !a.is_empty())
+            .collect::<Vec<_>>();
+        //`push, name, N` is the same as `push, N` for us
+        let value = arguments.iter().find_map(|a| a.parse::<usize>().ok());
+        match arguments.first() {
+            Some(&"push") => {
+                self.pack_stack.push(self.pack);
+                if value.is_some() {
+                    self.pack = value;
+                }
+            }
+            Some(&"pop") => self.pack = self.pack_stack.pop().flatten(),
+            _ => self.pack = value,
+        }
+    }
+
+    //Consumes `__attribute__((...))` and the like, returns whether one of them packs the type
+    fn attributes(&mut self) -> bool {
+        let start = self.pos;
+        while self
+            .significant()
+            .is_some_and(|t| t.kind == TokenKind::Identifier && is_attribute(&t.text))
+        {
+            self.pos += 1;
+            if !matches!(self.significant(), Some(t) if t.is_punct("(")) {
+                continue;
+            }
+            let mut depth = 0usize;
+            while let Some(token) = self.token() {
+                self.pos += 1;
+                if token.is_punct("(") {
+                    depth += 1;
+                } else if token.is_punct(")") {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
                     }
-                    if note.contains("[") {
-                        if let Some(captures) = field_code_regex.captures(note) {
-                            code = captures.get(1).unwrap().as_str();
-                            note = captures.get(2).unwrap().as_str();
-                        }
+                }
+            }
+        }
+        is_packed(&self.tokens[start..self.pos])
+    }
+
+    //Skips a declaration (or a function definition) we don't document
+    fn skip_declaration(&mut self) {
+        let mut depth = 0usize;
+        while let Some(token) = self.token() {
+            self.pos += 1;
+            if token.is_punct("{") {
+                depth += 1;
+            } else if token.is_punct("}") {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    let next = self.next_significant();
+                    if matches!(self.tokens.get(next), Some(t) if t.is_punct(";")) {
+                        self.pos = next + 1;
                     }
-                    curret_item.children.push(DocumentationItemChild {
-                        datatype: captures.get(1).unwrap().as_str().to_owned(),
-                        code: code.to_owned(),
-                        note: note.to_owned(),
-                        additional_data: data.to_owned(),
-                        signed: signed.to_owned(),
-                        bits: bits.to_owned(),
-                        msb: msb.to_owned(),
-                    });
+                    return;
                 }
-                //The name check may not be necesarry, but gonna leave it here just in case
-                else if line.contains(curret_item.name.as_str()) && line.contains('}') {
-                    data.items.push(curret_item.clone());
-                    parsing_state = ParsingState::None;
+            } else if token.is_punct(";") && depth == 0 {
+                return;
+            }
+        }
+    }
+
+    //Parses `[typedef] struct/enum/union [tag] { ... } [declarators];`, the keyword is the current token,
+    //`typedef` is the position of the `typedef` keyword
+    fn aggregate(&mut self, doc: Option<String>, typedef: Option<usize>) {
+        let start = typedef.unwrap_or(self.pos);
+        let keyword = self.token().unwrap();
+        let r#type = aggregate_type(keyword).unwrap();
+        self.pos += 1;
+        //`struct __attribute__((packed)) tag {`
+        let mut packed = self.attributes();
+        let tag = match self.significant() {
+            Some(t) if t.kind == TokenKind::Identifier => {
+                self.pos += 1;
+                Some(t.text.clone())
+            }
+            _ => None,
+        };
+        //Forward declarations, variables of an already declared type and functions returning one
+        if !matches!(self.significant(), Some(t) if t.is_punct("{")) {
+            if typedef.is_some() {
+                return self.skip_declaration();
+            }
+            self.pos = start;
+            return self.declaration(doc);
+        }
+        self.pos += 1;
+        let undocumented = self.undocumented.len();
+        let members = self.body(&r#type);
+
+        //For a typedef the declarators are the aliases, otherwise they are variables,
+        //without the closing brace the error is already reported and there are none to look for
+        let close = self.pos;
+        let declarators = match members.closed {
+            true => self.declarators(),
+            false => Vec::new(),
+        };
+        let location = Location::of(&self.tokens[start..self.pos], self.source);
+        packed |= is_packed(&self.tokens[close..self.pos]);
+        let alias = if typedef.is_some() {
+            declarators
+                .iter()
+                .find(|(_, pointer)| !pointer)
+                .or(declarators.first())
+                .map(|(name, _)| name.clone())
+        } else {
+            None
+        };
+
+        //Undocumented members only matter if the item is going to be documented
+        let undocumented = self.undocumented.split_off(undocumented);
+        let name = match alias.as_ref().or(tag.as_ref()) {
+            Some(name) => name.clone(),
+            //Nothing to refer to the type by
+            None => {
+                if doc.is_some() && members.closed {
+                    self.data.diagnostics.push(Diagnostic::warning(
+                        &keyword.span,
+                        format!("Documented {} has no name and is skipped", r#type.keyword()),
+                    ));
+                }
+                return;
+            }
+        };
+        let item = DocumentationItem {
+            r#type,
+            name,
+            tag,
+            alias,
+            children: members.children,
+            undocumented: members.undocumented,
+            location,
+            pack: if packed { Some(1) } else { self.pack },
+            ..Default::default()
+        };
+        match doc {
+            Some(doc) => {
+                self.data.diagnostics.extend(undocumented);
+                let doc = DocComment::parse(&doc);
+                self.data.items.push(DocumentationItem {
+                    note: doc.summary().to_owned(),
+                    doc,
+                    ..item
+                });
+            }
+            None => {
+                for name in [item.tag.clone(), item.alias.clone()].into_iter().flatten() {
+                    self.data.undocumented.insert(name, item.clone());
                 }
             }
         }
     }
 
-    Ok(data)
+    //Consumes the declarator list up to the `;`, returns the declared names and whether they are pointers
+    fn declarators(&mut self) -> Vec<(String, bool)> {
+        let mut declarators = Vec::new();
+        let mut name = None;
+        let mut pointer = false;
+        let mut depth = 0usize;
+        let mut last = None;
+        loop {
+            //Without the `;` the next declaration would be swallowed
+            let token = match self.token() {
+                Some(token) if depth > 0 || !starts_declaration(token) => token,
+                _ => {
+                    let span = last.or(self.token().map(|t| &t.span));
+                    if let Some(span) = span {
+                        self.data
+                            .diagnostics
+                            .push(Diagnostic::error(span, "Missing `;` after the declaration"));
+                    }
+                    if let Some(name) = name.take() {
+                        declarators.push((name, pointer));
+                    }
+                    break;
+                }
+            };
+            self.pos += 1;
+            if !token.is_comment() {
+                last = Some(&token.span);
+            }
+            if token.is_punct("(") || token.is_punct("[") || token.is_punct("{") {
+                depth += 1;
+            } else if token.is_punct(")") || token.is_punct("]") || token.is_punct("}") {
+                depth = depth.saturating_sub(1);
+            } else if depth > 0 {
+                continue;
+            } else if token.is_punct("*") {
+                pointer = true;
+            } else if token.kind == TokenKind::Identifier && !is_attribute(&token.text) {
+                name = Some(token.text.clone());
+            } else if token.is_punct(",") || token.is_punct(";") {
+                if let Some(name) = name.take() {
+                    declarators.push((name, pointer));
+                }
+                pointer = false;
+                if token.is_punct(";") {
+                    break;
+                }
+            }
+        }
+        declarators
+    }
+
+    //Parses the members of a struct/enum/union, the opening brace is expected to be consumed already
+    fn body(&mut self, r#type: &DocumentationType) -> Members {
+        let mut members = Members::default();
+        let tokens = self.tokens;
+        let open = &tokens[self.pos - 1];
+        if *r#type == DocumentationType::Enum {
+            self.next_enumerator = Some(0);
+        }
+        //Comments are left in place, the member may need them
+        loop {
+            let token = match self.tokens.get(self.next_significant()) {
+                //`typedef` can't be a member, the brace is missing
+                Some(token) if !token.is_ident("typedef") => token,
+                _ => {
+                    self.data.diagnostics.push(Diagnostic::error(
+                        &open.span,
+                        format!("Missing `}}` of the {}", r#type.keyword()),
+                    ));
+                    return members;
+                }
+            };
+            if token.is_punct("}") {
+                self.pos = self.next_significant() + 1;
+                members.closed = true;
+                return members;
+            }
+            match self.member(r#type) {
+                Some((child, true)) => members.children.push(child),
+                Some((child, false)) => members.undocumented.push((members.children.len(), child)),
+                None => {}
+            }
+        }
+    }
+
+    //Parses a single field or enumerator with its trailing comments, returns it and whether it's documented
+    fn member(&mut self, r#type: &DocumentationType) -> Option<(DocumentationItemChild, bool)> {
+        if *r#type != DocumentationType::Enum {
+            if let Some(keyword) = self.nested_keyword() {
+                return self.nested_member(keyword);
+            }
+        }
+        let terminator = if *r#type == DocumentationType::Enum {
+            ","
+        } else {
+            ";"
+        };
+        let mut declaration = Vec::new();
+        let mut leading = Vec::new();
+        let mut notes = Vec::new();
+        let mut depth = 0usize;
+        while let Some(token) = self.token() {
+            match token.kind {
+                //`//!` or `/** */` on the lines above
+                TokenKind::Comment(CommentKind::Doc) if declaration.is_empty() => {
+                    leading.push(token.text.clone())
+                }
+                TokenKind::Comment(CommentKind::DocTrailing) if depth == 0 => {
+                    notes.push(token.text.clone())
+                }
+                TokenKind::Comment(_) | TokenKind::Preprocessor => {}
+                _ if token.is_punct("}") && depth == 0 => break,
+                _ => {
+                    declaration.push(token.clone());
+                    if token.is_punct("{") || token.is_punct("(") || token.is_punct("[") {
+                        depth += 1;
+                    } else if token.is_punct("}") || token.is_punct(")") || token.is_punct("]") {
+                        depth = depth.saturating_sub(1);
+                    } else if token.is_punct(terminator) && depth == 0 {
+                        self.pos += 1;
+                        break;
+                    }
+                }
+            }
+            self.pos += 1;
+        }
+        self.trailing_notes(&mut notes);
+
+        if declaration.is_empty() {
+            return None;
+        }
+        let mut child = self.parse_note(&merge_notes(&leading, &notes));
+        if !leading.is_empty() && !notes.is_empty() {
+            self.trailing_columns(&mut child, &notes);
+        }
+        child.location = Location::of(&declaration, self.source);
+        child.datatype = join_tokens(&declaration);
+        split_declaration(&declaration, r#type, &mut child);
+        //Undocumented enumerators still count for the implicit numbering
+        if *r#type == DocumentationType::Enum {
+            child.value = self.enumerator_value(&child);
+        }
+        let span = &declaration[0].span;
+        if leading.is_empty() && notes.is_empty() {
+            let kind = match r#type {
+                DocumentationType::Enum => "Enumerator",
+                _ => "Field",
+            };
+            self.undocumented.push(Diagnostic::warning(
+                span,
+                format!("{} `{}` is not documented", kind, child.name),
+            ));
+            return Some((child, false));
+        }
+        self.check_annotation(&child, span);
+        Some((child, true))
+    }
+
+    //The parts of the annotation that didn't make it into the columns
+    fn check_annotation(&mut self, child: &DocumentationItemChild, span: &Span) {
+        for field in child.annotation.fields.iter().filter(|f| f.malformed) {
+            let kind = self.config.annotation.keys[&field.key].kind;
+            let message = match field.raw.split_once(' ') {
+                Some((_, trailing)) => format!(
+                    "`{}` after `{}:` is not part of its {} value, `{}:{}` is kept as text",
+                    trailing,
+                    field.key,
+                    kind.name(),
+                    field.key,
+                    field.raw
+                ),
+                None => format!(
+                    "`{}:{}` is not a valid {} value, it's kept as text",
+                    field.key,
+                    field.raw,
+                    kind.name()
+                ),
+            };
+            self.data
+                .diagnostics
+                .push(Diagnostic::warning(span, message));
+        }
+        if child
+            .note
+            .split_once(" //")
+            .is_some_and(|(_, rest)| rest.trim_start().starts_with('('))
+        {
+            self.data.diagnostics.push(Diagnostic::warning(
+                span,
+                "The annotation block is missing its `)` and is left in the description",
+            ));
+        }
+    }
+
+    //The code and the annotation are written after the field, even when the brief comes from above it
+    fn trailing_columns(&self, child: &mut DocumentationItemChild, notes: &[String]) {
+        let trailing = self.parse_note(&notes.join("\n"));
+        //The paragraph after the brief doesn't need them anymore
+        child.doc.details = child
+            .doc
+            .details
+            .replace(&trailing.doc.brief, &trailing.note);
+        if trailing.code != "-" {
+            child.code = trailing.code;
+        }
+        if trailing.annotation != Annotation::default() {
+            child.annotation = trailing.annotation;
+            child.additional_data = trailing.additional_data;
+            child.signed = trailing.signed;
+            child.bits = trailing.bits;
+            child.msb = trailing.msb;
+        }
+    }
+
+    //The explicit value if there is one, otherwise the previous value plus one
+    fn enumerator_value(&mut self, child: &DocumentationItemChild) -> Option<i64> {
+        let value = match &child.value_expression {
+            Some(expression) => self.constants.evaluate_str(expression),
+            None => self.next_enumerator,
+        };
+        self.next_enumerator = value.map(|v| v.wrapping_add(1));
+        if let Some(value) = value {
+            self.constants.values.insert(child.name.clone(), value);
+        }
+        value
+    }
+
+    //Comments after the terminator still belong to the member
+    fn trailing_notes(&mut self, notes: &mut Vec<String>) {
+        while let Some(token) = self.token() {
+            if token.kind != TokenKind::Comment(CommentKind::DocTrailing) {
+                break;
+            }
+            notes.push(token.text.clone());
+            self.pos += 1;
+        }
+    }
+
+    //Returns the position of the keyword if the next member declares a struct/union inline
+    fn nested_keyword(&self) -> Option<usize> {
+        let mut pos = self.next_significant();
+        while self.tokens.get(pos).is_some_and(|t| is_attribute(&t.text)) {
+            pos += 1;
+        }
+        let keyword = pos;
+        match self.tokens.get(pos).and_then(aggregate_type) {
+            Some(DocumentationType::Struct | DocumentationType::Union) => {}
+            _ => return None,
+        }
+        pos += 1;
+        if self
+            .tokens
+            .get(pos)
+            .is_some_and(|t| t.kind == TokenKind::Identifier)
+        {
+            pos += 1;
+        }
+        self.tokens
+            .get(pos)
+            .is_some_and(|t| t.is_punct("{"))
+            .then_some(keyword)
+    }
+
+    //Parses `struct/union [tag] { ... } [name];` inside of another aggregate
+    fn nested_member(&mut self, keyword: usize) -> Option<(DocumentationItemChild, bool)> {
+        //A doc comment right before the inner aggregate documents the aggregate itself
+        let leading = self.tokens[self.pos..keyword]
+            .iter()
+            .filter(|t| t.kind == TokenKind::Comment(CommentKind::Doc))
+            .map(|t| t.text.clone())
+            .next();
+        self.pos = keyword;
+        let tokens = self.tokens;
+        let span = &tokens[keyword].span;
+        let r#type = aggregate_type(&self.tokens[self.pos]).unwrap();
+        self.pos += 1;
+        let tag = match self.token() {
+            Some(t) if t.kind == TokenKind::Identifier => {
+                self.pos += 1;
+                Some(t.text.clone())
+            }
+            _ => None,
+        };
+        //Skip the opening brace
+        self.pos += 1;
+        let members = self.body(&r#type);
+        let close = self.pos;
+        let name = match members.closed {
+            true => self.declarators().into_iter().next(),
+            false => None,
+        }
+        .map(|(name, _)| name)
+        .unwrap_or_default();
+        let location = Location::of(&tokens[keyword..self.pos], self.source);
+        let packed = is_packed(&tokens[close..self.pos]);
+        let mut notes = Vec::new();
+        self.trailing_notes(&mut notes);
+
+        let documented = !notes.is_empty() || leading.is_some() || !members.children.is_empty();
+        if !documented {
+            self.undocumented.push(Diagnostic::warning(
+                span,
+                format!("Nested {} `{}` is not documented", r#type.keyword(), name),
+            ));
+        }
+        //The leading comment is the aggregate's, the field only falls back to it
+        let mut child = match notes.is_empty() {
+            true => self.parse_note(leading.as_deref().unwrap_or_default()),
+            false => self.parse_note(&notes.join("\n")),
+        };
+        if documented {
+            self.check_annotation(&child, span);
+        }
+        child.base_type = match &tag {
+            Some(tag) => format!("{} {}", r#type.keyword(), tag),
+            None => r#type.keyword().to_owned(),
+        };
+        child.datatype = format!("{} {{ ... }} {};", child.base_type, name).replace(" ;", ";");
+        child.name = name;
+        child.location = location.clone();
+        let doc = match leading {
+            Some(leading) => DocComment::parse(&leading),
+            None => child.doc.clone(),
+        };
+        child.nested = Some(Box::new(DocumentationItem {
+            note: doc.brief.clone(),
+            doc,
+            name: tag.clone().unwrap_or_else(|| child.name.clone()),
+            r#type,
+            tag,
+            children: members.children,
+            undocumented: members.undocumented,
+            location,
+            pack: if packed { Some(1) } else { self.pack },
+            ..Default::default()
+        }));
+        Some((child, documented))
+    }
+
+    //Splits the field comment into the note, the parameter code and the additional data
+    fn parse_note(&self, comment: &str) -> DocumentationItemChild {
+        let doc = DocComment::parse(comment);
+        let mut note = doc.brief.as_str();
+        let mut code = "-";
+        let mut annotation = Annotation::default();
+        if note.contains(" //") {
+            if let Some(captures) = self.additional_data_regex.captures(note) {
+                note = captures.get(1).unwrap().as_str();
+                annotation =
+                    Annotation::parse(captures.get(2).unwrap().as_str(), &self.config.annotation);
+            }
+        }
+        if note.contains('[') {
+            if let Some(captures) = self.field_code_regex.captures(note) {
+                code = captures.get(1).unwrap().as_str();
+                note = captures.get(2).unwrap().as_str();
+            }
+        }
+        //Anything without a column of its own goes to the remarks
+        let column = |column| annotation.column_text(&column).unwrap_or("-".to_owned());
+        let data = annotation.remarks();
+        DocumentationItemChild {
+            code: code.to_owned(),
+            note: note.to_owned(),
+            additional_data: match data.is_empty() {
+                true => "-".to_owned(),
+                false => data,
+            },
+            signed: column(Column::Sign),
+            bits: column(Column::Bits),
+            msb: column(Column::Scale),
+            annotation,
+            doc,
+            ..Default::default()
+        }
+    }
+}
+
+//A field documented both above and after its declaration gets the leading comment first and the trailing one
+//as a paragraph of its own after it, so the brief comes from the leading one, like with doxygen
+fn merge_notes(leading: &[String], trailing: &[String]) -> String {
+    [leading.join("\n\n"), trailing.join("\n")]
+        .into_iter()
+        .filter(|notes| !notes.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+//The parameters get their `@param` descriptions
+fn document_function(item: &mut DocumentationItem, doc: DocComment) {
+    for parameter in item.parameters.iter_mut() {
+        if let Some(param) = doc.params.iter().find(|p| p.name == parameter.name) {
+            parameter.direction = param.direction.clone().unwrap_or_default();
+            parameter.note = param.text.clone();
+        }
+    }
+    item.note = doc.summary().to_owned();
+    item.doc = doc;
+}
+
+//Tokens that can only be the start of the next declaration
+fn starts_declaration(token: &Token) -> bool {
+    matches!(
+        token.kind,
+        TokenKind::Comment(CommentKind::Doc) | TokenKind::Preprocessor
+    ) || token.is_ident("typedef")
+        || aggregate_type(token).is_some()
+}
+
+fn aggregate_type(token: &Token) -> Option<DocumentationType> {
+    match token.text.as_str() {
+        "struct" if token.kind == TokenKind::Identifier => Some(DocumentationType::Struct),
+        "enum" if token.kind == TokenKind::Identifier => Some(DocumentationType::Enum),
+        "union" if token.kind == TokenKind::Identifier => Some(DocumentationType::Union),
+        _ => None,
+    }
+}
+
+//`@{` and `@}`, or `\{` and `\}`
+fn is_group_marker(text: &str) -> bool {
+    matches!(text, "@{" | "@}" | "\\{" | "\\}")
+}
+
+//`__attribute__((packed))` or `__packed` among the tokens
+fn is_packed(tokens: &[Token]) -> bool {
+    tokens
+        .iter()
+        .any(|t| matches!(t.text.as_str(), "packed" | "__packed__" | "__packed"))
+}
+
+//Compiler specific keywords that can appear among the declarators
+fn is_attribute(ident: &str) -> bool {
+    matches!(
+        ident,
+        "__attribute__" | "__declspec" | "__packed" | "const" | "volatile"
+    )
+}
+
+//`ret name(params)`, returns None if the tokens aren't a function prototype
+fn parse_prototype(tokens: &[Token]) -> Option<DocumentationItem> {
+    let open = tokens.iter().position(|t| t.is_punct("("))?;
+    //`int x = foo(1, 2);` is a variable initialised by a call
+    if tokens[..open].iter().any(|t| t.is_punct("=")) {
+        return None;
+    }
+    let name = tokens.get(open.checked_sub(1)?)?;
+    //`void (*callback)(int);` is a variable and `FOO(x);` is a macro call
+    if name.kind != TokenKind::Identifier
+        || is_attribute(&name.text)
+        || open < 2
+        || tokens.get(open + 1).is_some_and(|t| t.is_punct("*"))
+    {
+        return None;
+    }
+    let mut depth = 0usize;
+    let mut close = None;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        if token.is_punct("(") {
+            depth += 1;
+        } else if token.is_punct(")") {
+            depth -= 1;
+            if depth == 0 {
+                close = Some(i);
+                break;
+            }
+        }
+    }
+    let close = close?;
+    //Only attributes may follow the parameter list, anything else means it isn't a prototype
+    if tokens[close + 1..]
+        .iter()
+        .any(|t| t.is_punct("=") || t.is_punct(","))
+    {
+        return None;
+    }
+
+    let return_type = tokens[..open - 1]
+        .iter()
+        .filter(|t| !matches!(t.text.as_str(), "extern" | "static" | "inline"))
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut parameters = Vec::new();
+    let mut parameter = Vec::new();
+    let mut depth = 0usize;
+    for token in &tokens[open + 1..close] {
+        if token.is_punct(",") && depth == 0 {
+            parameters.push(std::mem::take(&mut parameter));
+            continue;
+        }
+        if token.is_punct("(") || token.is_punct("[") {
+            depth += 1;
+        } else if token.is_punct(")") || token.is_punct("]") {
+            depth = depth.saturating_sub(1);
+        }
+        parameter.push(token.clone());
+    }
+    parameters.push(parameter);
+    let parameters = parameters
+        .into_iter()
+        .filter(|p| !(p.is_empty() || p.len() == 1 && p[0].is_ident("void")))
+        .map(|p| {
+            let identifiers = p.iter().filter(|t| t.kind == TokenKind::Identifier).count();
+            FunctionParameter {
+                //A lone type is an unnamed parameter
+                name: match identifiers > 1 {
+                    true => declared_name(&p),
+                    false => String::new(),
+                },
+                datatype: join_tokens(&p),
+                ..Default::default()
+            }
+        })
+        .collect();
+
+    Some(DocumentationItem {
+        r#type: DocumentationType::Function,
+        name: name.text.clone(),
+        return_type: join_tokens(&return_type),
+        parameters,
+        ..Default::default()
+    })
+}
+
+//Finds the declared identifier, `int (*name)(void);`, `char name[4];` and `int name : 3;` included
+fn declared_name(tokens: &[Token]) -> String {
+    declared_name_index(tokens).map_or_else(String::new, |i| tokens[i].text.clone())
+}
+
+fn declared_name_index(tokens: &[Token]) -> Option<usize> {
+    let mut name = None;
+    let mut depth = 0usize;
+    let mut function_pointer = false;
+    for (i, token) in tokens.iter().enumerate() {
+        if token.is_punct("(") {
+            depth += 1;
+            function_pointer = tokens.get(i + 1).is_some_and(|t| t.is_punct("*"));
+        } else if token.is_punct(")") {
+            depth = depth.saturating_sub(1);
+            function_pointer = false;
+        } else if depth == 0 && ["[", ":", "=", ",", ";"].iter().any(|p| token.is_punct(p)) {
+            break;
+        } else if token.kind == TokenKind::Identifier
+            && !is_attribute(&token.text)
+            && (depth == 0 || function_pointer)
+        {
+            name = Some(i);
+        }
+    }
+    name
+}
+
+//Fills in the structured parts of a field or enumerator declaration
+fn split_declaration(
+    tokens: &[Token],
+    r#type: &DocumentationType,
+    child: &mut DocumentationItemChild,
+) {
+    //The terminator isn't a part of the declaration
+    let tokens = match tokens.last() {
+        Some(t) if t.is_punct(";") || t.is_punct(",") => &tokens[..tokens.len() - 1],
+        _ => tokens,
+    };
+    let name = match r#type {
+        DocumentationType::Enum => tokens.iter().position(|t| t.kind == TokenKind::Identifier),
+        _ => declared_name_index(tokens),
+    };
+    let (before, after) = match name {
+        Some(name) => (&tokens[..name], &tokens[name + 1..]),
+        //Unnamed bitfield padding, `int : 3;`
+        None => tokens.split_at(
+            tokens
+                .iter()
+                .position(|t| t.is_punct(":"))
+                .unwrap_or(tokens.len()),
+        ),
+    };
+    child.name = name.map_or_else(String::new, |name| tokens[name].text.clone());
+
+    //`ret (*name)(params)`, the parenthesis before the name starts the declarator
+    let declarator = (0..before.len())
+        .find(|&i| before[i].is_punct("(") && before.get(i + 1).is_some_and(|t| t.is_punct("*")));
+    let (specifiers, pointers) = match declarator {
+        Some(open) => (&before[..open], &before[open + 1..]),
+        None => before.split_at(
+            before
+                .iter()
+                .position(|t| t.is_punct("*"))
+                .unwrap_or(before.len()),
+        ),
+    };
+    let mut base_type = Vec::new();
+    let mut depth = 0usize;
+    for token in specifiers {
+        if token.is_punct("(") {
+            depth += 1;
+        } else if token.is_punct(")") {
+            depth = depth.saturating_sub(1);
+        } else if declarator.is_some() && token.is_punct("*") {
+            //The whole return type of a function pointer is its base type
+            base_type.push(token.text.clone());
+        } else if depth > 0
+            || token.kind != TokenKind::Identifier
+            || token.text.starts_with("__")
+            || is_storage_class(&token.text)
+        {
+            //Attribute arguments, storage classes and other noise
+        } else if is_qualifier(&token.text) && declarator.is_none() {
+            child.qualifiers.push(token.text.clone());
+        } else {
+            base_type.push(token.text.clone());
+        }
+    }
+    child.base_type = base_type.join(" ");
+    for token in pointers {
+        if token.is_punct("*") {
+            child.pointer_depth += 1;
+            child.pointer_qualifiers.push(Vec::new());
+        } else if is_qualifier(&token.text) {
+            if let Some(qualifiers) = child.pointer_qualifiers.last_mut() {
+                qualifiers.push(token.text.clone());
+            }
+        }
+    }
+
+    let mut depth = 0usize;
+    let mut dimension = Vec::new();
+    for (i, token) in after.iter().enumerate() {
+        if token.is_punct("[") || token.is_punct("(") {
+            depth += 1;
+            if depth == 1 {
+                dimension.clear();
+                continue;
+            }
+        } else if token.is_punct("]") || token.is_punct(")") {
+            //The parenthesis closing the declarator of a function pointer
+            if depth == 0 {
+                continue;
+            }
+            depth -= 1;
+            if depth == 0 {
+                if token.is_punct("]") {
+                    child.array_dims.push(join_tokens(&dimension));
+                } else if declarator.is_some() {
+                    child.function = Some(join_tokens(&dimension));
+                }
+                continue;
+            }
+        } else if depth == 0 && token.is_punct(":") {
+            child.bit_width = Some(join_tokens(&after[i + 1..]));
+            break;
+        } else if depth == 0 && token.is_punct("=") {
+            child.value_expression = Some(join_tokens(&after[i + 1..]));
+            break;
+        }
+        dimension.push(token.clone());
+    }
+}
+
+fn is_qualifier(ident: &str) -> bool {
+    matches!(ident, "const" | "volatile" | "restrict" | "_Atomic")
+}
+
+//Where the object lives, not a part of its type
+fn is_storage_class(ident: &str) -> bool {
+    matches!(
+        ident,
+        "static" | "extern" | "register" | "auto" | "mutable" | "thread_local" | "_Thread_local"
+    )
+}
+
+//Rebuilds the declaration text from the tokens, any whitespace between them becomes a single space
+fn join_tokens(tokens: &[Token]) -> String {
+    let mut text = String::new();
+    let mut previous_end = None;
+    for token in tokens {
+        if matches!(previous_end, Some(end) if token.span.start > end) {
+            text.push(' ');
+        }
+        text += &token.text;
+        previous_end = Some(token.span.end);
+    }
+    text
 }
 
 //A whole bunch of tests, which I didn't really need, I was trying to find a bug I had, but it
@@ -169,6 +1682,7 @@ fn parse_simple_file_test() {
         note: "Test struct".to_owned(),
         name: "test".to_owned(),
         children: Vec::default(),
+        ..Default::default()
     };
     assert_eq!(data.r#type, expected.r#type);
     assert_eq!(data.note, expected.note);
@@ -197,14 +1711,15 @@ fn parse_simple_file_test() {
 #[test]
 fn parse_file_with_multiple_stucts_test() {
     let data = parse_file(PathBuf::from("test_data/test3.h"), encoding::all::UTF_8).unwrap();
-    assert_eq!(data.items.len(), 2);
-    let data = data.items[1].clone();
+    assert_eq!(data.items.len(), 4);
+    let data = data.items[3].clone();
     assert_eq!(data.children.len(), 2);
     let expected = DocumentationItem {
         r#type: DocumentationType::Struct,
         note: "Test struct".to_owned(),
         name: "test".to_owned(),
         children: Vec::default(),
+        ..Default::default()
     };
     assert_eq!(data.r#type, expected.r#type);
     assert_eq!(data.note, expected.note);
@@ -233,14 +1748,15 @@ fn parse_file_with_multiple_stucts_test() {
 #[test]
 fn parse_file_with_enum_test() {
     let data = parse_file(PathBuf::from("test_data/test4.h"), encoding::all::UTF_8).unwrap();
-    assert_eq!(data.items.len(), 2);
-    let data = data.items[1].clone();
+    assert_eq!(data.items.len(), 4);
+    let data = data.items[3].clone();
     assert_eq!(data.children.len(), 2);
     let expected = DocumentationItem {
         r#type: DocumentationType::Enum,
         note: "Test struct".to_owned(),
         name: "test".to_owned(),
         children: Vec::default(),
+        ..Default::default()
     };
     assert_eq!(data.r#type, expected.r#type);
     assert_eq!(data.note, expected.note);
@@ -269,14 +1785,15 @@ fn parse_file_with_enum_test() {
 #[test]
 fn parse_file_with_defines_test() {
     let data = parse_file(PathBuf::from("test_data/test2.h"), encoding::all::UTF_8).unwrap();
-    assert_eq!(data.items.len(), 1);
-    let data = data.items[0].clone();
+    assert_eq!(data.items.len(), 3);
+    let data = data.items[2].clone();
     assert_eq!(data.children.len(), 2);
     let expected = DocumentationItem {
         r#type: DocumentationType::Struct,
         note: "Test struct".to_owned(),
         name: "test".to_owned(),
         children: Vec::default(),
+        ..Default::default()
     };
     assert_eq!(data.r#type, expected.r#type);
     assert_eq!(data.note, expected.note);
@@ -305,8 +1822,8 @@ fn parse_file_with_defines_test() {
 #[test]
 fn parse_file_with_additional_note_test() {
     let data = parse_file(PathBuf::from("test_data/test5.h"), encoding::all::UTF_8).unwrap();
-    assert_eq!(data.items.len(), 2);
-    let data = data.items[1].clone();
+    assert_eq!(data.items.len(), 4);
+    let data = data.items[3].clone();
     assert_eq!(data.children.len(), 2);
     let expected = DocumentationItem {
         r#type: DocumentationType::Struct,
@@ -338,3 +1855,631 @@ fn parse_file_with_additional_note_test() {
     assert_eq!(child.datatype, expected.datatype);
     assert_eq!(child.note, expected.note);
 }
+
+#[test]
+fn parse_file_with_unusual_formatting_test() {
+    let data = parse_file(PathBuf::from("test_data/test6.h"), encoding::all::UTF_8).unwrap();
+    assert_eq!(data.items.len(), 2);
+    let item = data.items[0].clone();
+    assert_eq!(item.name, "test");
+    assert_eq!(item.note, "Test struct");
+    assert_eq!(item.children.len(), 2);
+    assert_eq!(item.children[0].datatype, "int a;");
+    assert_eq!(item.children[1].datatype, "unsigned long b;");
+    assert_eq!(item.children[1].note, "This is B");
+
+    let item = data.items[1].clone();
+    assert_eq!(item.r#type, DocumentationType::Enum);
+    assert_eq!(item.name, "test_enum");
+    assert_eq!(item.children.len(), 2);
+    assert_eq!(item.children[0].datatype, "a = 1,");
+    assert_eq!(item.children[1].datatype, "b");
+    assert_eq!(item.children[1].note, "This is B");
+}
+
+#[test]
+fn parse_file_with_tags_and_aliases_test() {
+    let data = parse_file(PathBuf::from("test_data/test7.h"), encoding::all::UTF_8).unwrap();
+    assert_eq!(data.items.len(), 4);
+
+    let item = data.items[0].clone();
+    assert_eq!(item.name, "plain");
+    assert_eq!(item.tag.as_deref(), Some("plain"));
+    assert_eq!(item.alias, None);
+    assert_eq!(item.children.len(), 1);
+
+    let item = data.items[1].clone();
+    assert_eq!(item.name, "anonymous_t");
+    assert_eq!(item.tag, None);
+    assert_eq!(item.alias.as_deref(), Some("anonymous_t"));
+
+    let item = data.items[2].clone();
+    assert_eq!(item.name, "tagged_t");
+    assert_eq!(item.tag.as_deref(), Some("tagged_s"));
+    assert_eq!(item.alias.as_deref(), Some("tagged_t"));
+
+    let item = data.items[3].clone();
+    assert_eq!(item.r#type, DocumentationType::Enum);
+    assert_eq!(item.name, "plain_enum");
+    assert_eq!(item.children.len(), 1);
+}
+
+#[test]
+fn parse_file_with_union_test() {
+    let data = parse_file(PathBuf::from("test_data/test8.h"), encoding::all::UTF_8).unwrap();
+    assert_eq!(data.items.len(), 1);
+    let item = data.items[0].clone();
+    assert_eq!(item.r#type, DocumentationType::Union);
+    assert_eq!(item.name, "word_t");
+    assert_eq!(item.tag.as_deref(), Some("word_u"));
+    assert_eq!(item.children.len(), 3);
+    assert_eq!(item.children[0].datatype, "unsigned int raw;");
+    assert_eq!(item.children[1].note, "Word halves");
+    assert_eq!(item.children[2].datatype, "unsigned char bytes[4];");
+}
+
+#[test]
+fn parse_file_with_nested_aggregates_test() {
+    let data = parse_file(PathBuf::from("test_data/test9.h"), encoding::all::UTF_8).unwrap();
+    assert_eq!(data.items.len(), 1);
+    let item = data.items[0].clone();
+    assert_eq!(item.name, "message_t");
+    assert_eq!(item.children.len(), 3);
+
+    let header = item.children[0].clone();
+    assert_eq!(header.name, "header");
+    assert_eq!(header.note, "Header");
+    let nested = header.nested.unwrap();
+    assert_eq!(nested.r#type, DocumentationType::Struct);
+    assert_eq!(nested.note, "Message header");
+    assert_eq!(nested.name, "header_s");
+    assert_eq!(nested.children.len(), 2);
+    assert_eq!(nested.children[1].name, "flags");
+    assert_eq!(nested.children[1].datatype, "unsigned int flags : 8;");
+
+    let payload = item.children[1].clone();
+    assert_eq!(payload.name, "");
+    let nested = payload.nested.unwrap();
+    assert_eq!(nested.r#type, DocumentationType::Union);
+    assert_eq!(nested.children.len(), 2);
+
+    assert_eq!(item.children[2].name, "checksum");
+    assert_eq!(item.children[2].note, "Checksum");
+}
+
+#[test]
+fn parse_file_with_all_comment_forms_test() {
+    let data = parse_file(PathBuf::from("test_data/test10.h"), encoding::all::UTF_8).unwrap();
+    assert_eq!(data.items.len(), 3);
+
+    let item = data.items[0].clone();
+    assert_eq!(item.note, "Javadoc style struct");
+    let notes = item
+        .children
+        .iter()
+        .map(|c| c.note.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        notes,
+        vec![
+            "This is A",
+            "This is B",
+            "This is C, continued",
+            "This is D continued"
+        ]
+    );
+
+    let item = data.items[1].clone();
+    assert_eq!(item.note, "Slashes style enum with a second line");
+    assert_eq!(item.children.len(), 2);
+    assert_eq!(item.children[1].note, "This is B");
+
+    let item = data.items[2].clone();
+    assert_eq!(item.note, "Qt style struct");
+    assert_eq!(item.children[0].note, "This is A");
+}
+
+#[test]
+fn parse_file_with_doxygen_commands_test() {
+    let data = parse_file(PathBuf::from("test_data/test11.h"), encoding::all::UTF_8).unwrap();
+    assert_eq!(data.items.len(), 1);
+    let item = data.items[0].clone();
+    assert_eq!(item.note, "Status word");
+    assert_eq!(item.doc.details, "Sent once per second.");
+    assert_eq!(item.doc.tag("since"), Some("2.1"));
+
+    let child = item.children[0].clone();
+    assert_eq!(child.note, "Operating mode");
+    assert_eq!(child.doc.notes, vec!["Only modes 0..3 are used"]);
+    let child = item.children[1].clone();
+    assert_eq!(child.note, "Temperature");
+    assert_eq!(child.doc.warnings, vec!["Not calibrated"]);
+    let child = item.children[2].clone();
+    assert_eq!(child.note, "Old field");
+    assert_eq!(child.doc.deprecated.as_deref(), Some("Use mode instead"));
+}
+
+#[test]
+fn parse_defines_test() {
+    let data = parse_file(PathBuf::from("test_data/test2.h"), encoding::all::UTF_8).unwrap();
+    let defines = data
+        .items
+        .iter()
+        .filter(|i| i.r#type == DocumentationType::Define)
+        .collect::<Vec<_>>();
+    assert_eq!(defines.len(), 2);
+    assert_eq!(defines[0].name, "stuff");
+    assert_eq!(defines[0].value, "124215");
+    assert_eq!(defines[0].note, "This is a define");
+    assert_eq!(defines[1].name, "more_stuff");
+    assert_eq!(defines[1].value, "25125125");
+    assert_eq!(defines[1].note, "satasjt");
+
+    let data = parse_source(
+        "#define GUARD_H\n\
+         //! Bigger of two\n\
+         #define MAX(a, b) ((a) > (b) ? (a) : (b))\n\
+         #  define MSG_ID_START (1u << 4) /**< Start */",
+    );
+    assert_eq!(data.items.len(), 2);
+    assert_eq!(data.items[0].name, "MAX(a, b)");
+    assert_eq!(data.items[0].value, "((a) > (b) ? (a) : (b))");
+    assert_eq!(data.items[1].name, "MSG_ID_START");
+    assert_eq!(data.items[1].value, "(1u << 4)");
+    assert_eq!(data.items[1].note, "Start");
+}
+
+#[test]
+fn parse_file_with_functions_test() {
+    let data = parse_file(PathBuf::from("test_data/test12.h"), encoding::all::UTF_8).unwrap();
+    assert_eq!(data.items.len(), 5);
+
+    let item = data.items[0].clone();
+    assert_eq!(item.r#type, DocumentationType::Function);
+    assert_eq!(item.name, "send_message");
+    assert_eq!(item.note, "Sends a message");
+    assert_eq!(item.return_type, "int");
+    assert_eq!(item.doc.returns, "0 on success");
+    assert_eq!(item.parameters.len(), 2);
+    assert_eq!(item.parameters[0].datatype, "unsigned int id");
+    assert_eq!(item.parameters[0].name, "id");
+    assert_eq!(item.parameters[0].direction, "in");
+    assert_eq!(item.parameters[0].note, "Message id");
+    assert_eq!(item.parameters[1].name, "reply");
+    assert_eq!(item.parameters[1].direction, "out");
+
+    let item = data.items[1].clone();
+    assert_eq!(item.name, "reset");
+    assert_eq!(item.return_type, "void");
+    assert!(item.parameters.is_empty());
+
+    let item = data.items[2].clone();
+    assert_eq!(item.name, "twice");
+    assert_eq!(item.return_type, "int");
+    assert_eq!(item.parameters[0].name, "x");
+
+    //The return type starts with a tag keyword, but there's no body
+    let item = data.items[3].clone();
+    assert_eq!(item.name, "make_message");
+    assert_eq!(item.note, "Creates a message");
+    assert_eq!(item.return_type, "struct message_s *");
+    let item = data.items[4].clone();
+    assert_eq!(item.name, "get_mode");
+    assert_eq!(item.return_type, "enum mode");
+    //A variable initialised by a call isn't the function it calls
+    assert!(data.items.iter().all(|i| i.name != "foo"));
+}
+
+#[test]
+fn split_declaration_test() {
+    let data = parse_source(
+        "//! Test\n\
+         struct test {\n\
+           const volatile unsigned long *const *ptr; //!< Pointer\n\
+           char name[4][NAME_LEN + 1]; //!< Array\n\
+           test_test Test : 6; //!< Bitfield\n\
+           void (*callback)(int, char *); //!< Callback\n\
+           struct header_s header; //!< Struct\n\
+           char *(*name_of)(void); //!< Returns a pointer\n\
+           static const int limit; //!< Storage class\n\
+         };\n\
+         //! Test\n\
+         enum test_enum {\n\
+           TEST_test = 1 << 3, //!< Value\n\
+           TEST_next //!< Implicit\n\
+         };",
+    );
+    let children = &data.items[0].children;
+    assert_eq!(children[0].name, "ptr");
+    assert_eq!(children[0].base_type, "unsigned long");
+    assert_eq!(children[0].qualifiers, vec!["const", "volatile"]);
+    assert_eq!(children[0].pointer_depth, 2);
+    assert_eq!(
+        children[0].pointer_qualifiers,
+        vec![vec!["const".to_owned()], vec![]]
+    );
+    assert_eq!(
+        children[0].declaration(),
+        "const volatile unsigned long *const *ptr"
+    );
+    assert_eq!(children[1].name, "name");
+    assert_eq!(children[1].base_type, "char");
+    assert_eq!(children[1].array_dims, vec!["4", "NAME_LEN + 1"]);
+    assert_eq!(children[1].declaration(), "char name[4][NAME_LEN + 1]");
+    assert_eq!(
+        children[1].declaration_named("outer.name"),
+        "char outer.name[4][NAME_LEN + 1]"
+    );
+    assert_eq!(children[2].name, "Test");
+    assert_eq!(children[2].base_type, "test_test");
+    assert_eq!(children[2].bit_width.as_deref(), Some("6"));
+    assert_eq!(children[2].datatype, "test_test Test : 6;");
+    assert_eq!(children[2].declaration(), "test_test Test : 6");
+    assert_eq!(children[3].name, "callback");
+    //A pointer to a function returning void
+    assert_eq!(children[3].base_type, "void");
+    assert_eq!(children[3].pointer_depth, 1);
+    assert_eq!(children[3].function.as_deref(), Some("int, char *"));
+    assert!(children[3].array_dims.is_empty());
+    assert_eq!(children[3].declaration(), "void (*callback)(int, char *)");
+    assert_eq!(children[4].base_type, "struct header_s");
+    assert_eq!(children[5].base_type, "char *");
+    assert_eq!(children[5].function.as_deref(), Some("void"));
+    assert_eq!(children[5].pointer_depth, 1);
+    //Storage classes aren't qualifiers of the type
+    assert_eq!(children[6].qualifiers, vec!["const"]);
+    assert_eq!(children[6].base_type, "int");
+    assert_eq!(children[6].declaration(), "static const int limit");
+
+    let children = &data.items[1].children;
+    assert_eq!(children[0].name, "TEST_test");
+    assert_eq!(children[0].value_expression.as_deref(), Some("1 << 3"));
+    assert_eq!(children[0].declaration(), "TEST_test = 1 << 3");
+    assert_eq!(children[1].name, "TEST_next");
+    assert_eq!(children[1].value_expression, None);
+}
+
+#[test]
+fn enum_values_test() {
+    let data = parse_file(PathBuf::from("test_data/test13.h"), encoding::all::UTF_8).unwrap();
+    assert_eq!(data.items.len(), 1);
+    let values = data.items[0]
+        .children
+        .iter()
+        .map(|c| (c.name.as_str(), c.value))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        values,
+        vec![
+            ("KIND_NONE", Some(0)),
+            ("KIND_FIRST", Some(16)),
+            ("KIND_SECOND", Some(17)),
+            ("KIND_AFTER_HIDDEN", Some(19)),
+            ("KIND_CHAR", Some(65)),
+            ("KIND_OCTAL", Some(8)),
+            ("KIND_FLAGS", Some(5)),
+            ("KIND_LAST", Some(22)),
+            ("KIND_UNKNOWN", None),
+            ("KIND_NEXT", None),
+        ]
+    );
+}
+
+#[test]
+fn typed_annotations_test() {
+    use crate::annotation::{BitRange, ScaleKind, Sign};
+    let data = parse_file(PathBuf::from("test_data/test14.h"), encoding::all::UTF_8).unwrap();
+    let children = &data.items[0].children;
+    assert_eq!(children.len(), 4);
+
+    let latitude = &children[0].annotation;
+    assert_eq!(children[0].signed, "4");
+    assert_eq!(latitude.sign, Some(Sign::Bit(4)));
+    assert_eq!(latitude.bits, Some(BitRange { first: 5, last: 19 }));
+    assert_eq!(latitude.scale.unwrap().kind, ScaleKind::Lsb);
+    assert_eq!(latitude.lsb(), Some(0.001));
+
+    let altitude = &children[1].annotation;
+    assert_eq!(altitude.sign, Some(Sign::Signed));
+    assert_eq!(altitude.scale.unwrap().kind, ScaleKind::Msb);
+    assert_eq!(altitude.lsb(), Some(1.0));
+
+    //No `signed:`, which the old regex needed
+    assert_eq!(children[2].annotation.msb(), Some(64.0));
+    assert_eq!(children[3].annotation.scale, None);
+    assert_eq!(children[3].additional_data, "min:0 max:360");
+}
+
+#[test]
+fn configured_annotations_test() {
+    use crate::annotation::Value;
+    let config = Config::from_toml(
+        "[annotation.keys.unit]\n\
+         column = \"Единицы\"\n\
+         [annotation.keys.default]\n\
+         type = \"integer\"\n\
+         [annotation.keys.range]\n\
+         type = \"range\"\n\
+         [annotation.keys.endian]\n\
+         column = \"sign\"\n",
+    )
+    .unwrap();
+    let data = parse_file_with_config(
+        PathBuf::from("test_data/test15.h"),
+        encoding::all::UTF_8,
+        &config,
+    )
+    .unwrap();
+    let children = &data.items[0].children;
+    assert_eq!(children.len(), 3);
+    assert_eq!(children[0].note, "Speed");
+    assert_eq!(
+        children[0]
+            .annotation
+            .column_text(&Column::Custom("Единицы".to_owned())),
+        Some("m/s".to_owned())
+    );
+    assert_eq!(children[0].annotation.fields[1].value, Value::Integer(0));
+    assert_eq!(children[0].additional_data, "default:0 range:0..300");
+    assert_eq!(children[0].bits, "0..15");
+    assert_eq!(children[1].signed, "little");
+    //Unknown keys end up in the remarks as they were written
+    assert_eq!(children[2].additional_data, "see ICD owner:nav-team");
+
+    //Without the config the same keys are unknown
+    let data = parse_file(PathBuf::from("test_data/test15.h"), encoding::all::UTF_8).unwrap();
+    assert_eq!(
+        data.items[0].children[0].additional_data,
+        "unit:m/s default:0 range:0..300"
+    );
+}
+
+#[test]
+fn preprocessor_variants_test() {
+    let parse = |defines: &[&str]| {
+        let mut config = Config::default();
+        for define in defines {
+            config.preprocessor.define(define);
+        }
+        parse_file_with_config(
+            PathBuf::from("test_data/test16.h"),
+            encoding::all::UTF_8,
+            &config,
+        )
+        .unwrap()
+    };
+
+    let data = parse(&[]);
+    assert_eq!(data.items.len(), 2);
+    assert_eq!(data.items[0].note, "Basic header");
+    let values = data.items[1]
+        .children
+        .iter()
+        .map(|c| (c.name.as_str(), c.value))
+        .collect::<Vec<_>>();
+    assert_eq!(values, vec![("CMD_RESET", Some(0)), ("CMD_LAST", Some(1))]);
+
+    let data = parse(&["LEGACY_PROTOCOL"]);
+    assert_eq!(data.items[0].note, "Old header");
+
+    let data = parse(&["VARIANT=2"]);
+    assert_eq!(data.items[0].note, "New header");
+    assert_eq!(data.items[0].children.len(), 2);
+    assert_eq!(data.items[1].children.len(), 3);
+    assert_eq!(data.items[1].children[2].value, Some(2));
+}
+
+#[test]
+fn parse_file_with_diagnostics_test() {
+    use crate::diagnostics::Severity;
+    let data = parse_file(PathBuf::from("test_data/test18.h"), encoding::all::UTF_8).unwrap();
+    //Every broken item is still there
+    let names = data
+        .items
+        .iter()
+        .map(|i| i.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        vec!["first_t", "second_t", "third_s", "fourth_t", "fifth_s"]
+    );
+    assert_eq!(data.items[0].children.len(), 3);
+    //Whatever was parsed before the brace went missing is kept
+    assert_eq!(data.items[2].children.len(), 1);
+    assert_eq!(data.items[4].children.len(), 2);
+
+    let diagnostics = data
+        .diagnostics
+        .iter()
+        .map(|d| (d.severity, d.line, d.column, d.message.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        diagnostics,
+        vec![
+            (Severity::Warning, 4, 3, "Field `b` is not documented"),
+            (
+                Severity::Warning,
+                5,
+                3,
+                "`bits:x..y` is not a valid bits value, it's kept as text"
+            ),
+            (
+                Severity::Warning,
+                6,
+                3,
+                "The annotation block is missing its `)` and is left in the description"
+            ),
+            (Severity::Error, 12, 3, "Missing `;` after the declaration"),
+            (Severity::Error, 15, 24, "Missing `}` of the struct"),
+            (Severity::Error, 28, 16, "Missing `}` of the struct"),
+        ]
+    );
+    assert!(data.diagnostics[0]
+        .to_string()
+        .starts_with("test_data/test18.h:4:3: warning:"));
+}
+
+#[test]
+fn source_locations_test() {
+    let data = parse_file(PathBuf::from("test_data/test19.h"), encoding::all::UTF_8).unwrap();
+    let lines = |l: &Location| (l.start_line, l.end_line);
+    let define = &data.items[0].location;
+    assert_eq!(lines(define), (2, 3));
+    assert_eq!(define.file, PathBuf::from("test_data/test19.h"));
+    assert_eq!(define.to_string(), "test19.h:2-3");
+
+    let packet = &data.items[1];
+    assert_eq!(lines(&packet.location), (6, 13));
+    let children = &packet.children;
+    assert_eq!(lines(&children[0].location), (7, 7));
+    assert_eq!(lines(&children[1].location), (8, 10));
+    assert_eq!(lines(&children[2].location), (11, 12));
+    let nested = children[1].nested.as_ref().unwrap();
+    assert_eq!(lines(&nested.children[0].location), (9, 9));
+    assert_eq!(
+        nested.children[0].location.file,
+        PathBuf::from("test_data/test19.h")
+    );
+    //The byte span covers the declaration and nothing else
+    let source = std::fs::read_to_string("test_data/test19.h").unwrap();
+    let id = &children[0].location;
+    assert_eq!(&source[id.start..id.end], "unsigned int id;");
+
+    assert_eq!(lines(&data.items[2].location), (16, 16));
+    assert_eq!(data.items[2].location.lines(), "16");
+}
+
+#[test]
+fn detect_file_encoding_test() {
+    let path = PathBuf::from("test_data/test20.h");
+    let data = parse_file(path.clone(), AUTO).unwrap();
+    assert_eq!(data.encoding.as_deref(), Some("windows-1251"));
+    assert_eq!(data.items[0].note, "Заголовок сообщения");
+    assert_eq!(data.items[0].children[0].note, "Длина сообщения в словах");
+    //Only `auto` is reported
+    let data = parse_file(path, encoding::all::WINDOWS_1251).unwrap();
+    assert_eq!(data.encoding, None);
+}
+
+#[test]
+fn strict_decoding_test() {
+    let path = PathBuf::from("test_data/test21.h");
+    let data = parse_file(path.clone(), encoding::all::UTF_8).unwrap();
+    assert_eq!(data.items[0].children[1].note, "Bad bytes");
+    assert_eq!(
+        data.diagnostics[0].to_string(),
+        "test_data/test21.h:4:19: warning: Invalid utf-8 bytes `FF FE` at byte 65 are dropped"
+    );
+
+    let mut config = Config::default();
+    config.decoding.strict = true;
+    let error = parse_file_with_config(path, encoding::all::UTF_8, &config).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(
+        error.to_string(),
+        "invalid utf-8 bytes `FF FE` at byte 65 (line 4, column 19)"
+    );
+}
+
+#[test]
+fn parse_reader_test() {
+    let bytes = std::fs::read("test_data/test20.h").unwrap();
+    let name = Path::new("HEAD:test_data/test20.h");
+    let data = parse_reader(std::io::Cursor::new(bytes), name, AUTO).unwrap();
+    assert_eq!(data.items[0].note, "Заголовок сообщения");
+    assert_eq!(data.items[0].location.file, name);
+
+    let data = parse_str(
+        "//! A\ntypedef struct {\n  int a;\n} a_t;",
+        Path::new("<stdin>"),
+    );
+    assert_eq!(data.items[0].name, "a_t");
+    assert_eq!(
+        data.diagnostics[0].to_string(),
+        "<stdin>:3:3: warning: Field `a` is not documented"
+    );
+}
+
+#[test]
+fn packed_structs_test() {
+    let data = parse_file(PathBuf::from("test_data/test22.h"), encoding::all::UTF_8).unwrap();
+    let packs = data.items.iter().map(|i| i.pack).collect::<Vec<_>>();
+    //The pragma is popped after `pragma_t`
+    assert_eq!(packs, [None, Some(1), Some(1), None, None, None, None]);
+    //The undocumented `flags` is kept for the layout, before the third child
+    let mixed = &data.items[0];
+    assert_eq!(mixed.children.len(), 8);
+    assert_eq!(mixed.undocumented.len(), 1);
+    assert_eq!(mixed.undocumented[0].0, 2);
+    assert_eq!(mixed.undocumented[0].1.name, "flags");
+    assert_eq!(data.typedefs["word_t"].base_type, "uint16_t");
+    assert_eq!(data.typedefs["handler_t"].pointer_depth, 1);
+    //Undocumented aggregates are only kept for the layout
+    assert_eq!(data.undocumented["inner_t"].undocumented.len(), 1);
+    assert_eq!(data.undocumented["mode_t"].r#type, DocumentationType::Enum);
+}
+
+#[test]
+fn leading_field_comments_test() {
+    use crate::annotation::Sign;
+    let data = parse_file(PathBuf::from("test_data/test24.h"), encoding::all::UTF_8).unwrap();
+    let item = &data.items[0];
+    let notes = item
+        .children
+        .iter()
+        .map(|c| c.note.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        notes,
+        [
+            "Above, slashes",
+            "Above, javadoc on two lines",
+            "Above",
+            "Only after",
+            "Above, three slashes",
+            "Message length"
+        ]
+    );
+    //Both: the leading one first, the trailing one as a paragraph after it
+    assert_eq!(item.children[2].doc.details, "After");
+    assert_eq!(item.children[3].code, "D1");
+    assert_eq!(item.children[4].doc.notes, ["Careful"]);
+    //The code and the annotation come from the trailing comment all the same
+    let len = &item.children[5];
+    assert_eq!(len.note, "Message length");
+    assert_eq!(len.code, "LEN");
+    assert_eq!(len.signed, "нет");
+    assert_eq!(len.bits, "16");
+    assert_eq!(len.annotation.sign, Some(Sign::Unsigned));
+    assert_eq!(len.doc.details, "Length");
+    assert_eq!(item.undocumented[0].1.name, "f");
+    assert_eq!(
+        data.diagnostics[0].to_string(),
+        "test_data/test24.h:16:3: warning: Field `f` is not documented"
+    );
+
+    let notes = data.items[1]
+        .children
+        .iter()
+        .map(|c| c.note.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(notes, ["Off", "On"]);
+}
+
+#[test]
+fn item_descriptions_test() {
+    let data = parse_file(PathBuf::from("test_data/test25.h"), encoding::all::UTF_8).unwrap();
+    //Every block above the typedef is collected, the caption only gets the first sentence
+    let header = &data.items[0];
+    //The file comments above it aren't, one is separated by an empty line, the other one is `@file`
+    assert_eq!(header.note, "Message header");
+    assert!(header.doc.tags.is_empty());
+    assert_eq!(
+        header.doc.description(),
+        "Sent before every message on the bus.\nThe id is unique per device. Ids below 16 are reserved."
+    );
+
+    let send = &data.items[1];
+    assert_eq!(send.note, "Sends a message. Blocks. Retries three times");
+    assert_eq!(send.doc.description(), "");
+}
@@ -0,0 +1,222 @@
+//The GPLv3 License (GPLv3)
+//
+//Copyright (c) 2023 Ciubix8513
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    diagnostics::Diagnostic,
+    eval::{evaluate, Constants},
+    lexer::{tokenize, Span, Token, TokenKind},
+};
+use std::collections::HashMap;
+
+//A single `#if ... #endif` block
+struct Conditional {
+    //Whether the code around the block is compiled at all
+    parent_active: bool,
+    //Whether one of the branches was already chosen
+    taken: bool,
+    active: bool,
+    //Where the `#if` is, for when the `#endif` is missing
+    span: Span,
+}
+
+//Drops the tokens of the inactive `#if`/`#ifdef`/`#else` branches together with the conditionals
+//themselves, macros defined by the file are taken into account as they appear
+pub fn preprocess(
+    tokens: Vec<Token>,
+    defines: &HashMap<String, String>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Vec<Token> {
+    let mut constants = Constants {
+        macros: defines.clone(),
+        undefined: Some(0),
+        ..Default::default()
+    };
+    let mut stack: Vec<Conditional> = Vec::new();
+    let mut output = Vec::new();
+    for token in tokens {
+        let active = stack.last().is_none_or(|c| c.active);
+        let (name, rest) = match token.kind {
+            TokenKind::Preprocessor => directive(&token.text).unwrap_or_default(),
+            _ => {
+                if active {
+                    output.push(token);
+                }
+                continue;
+            }
+        };
+        match name {
+            "if" | "ifdef" | "ifndef" => {
+                let condition = active && condition(name, rest, &constants);
+                stack.push(Conditional {
+                    parent_active: active,
+                    taken: condition,
+                    active: condition,
+                    span: token.span,
+                });
+            }
+            "elif" | "elifdef" | "elifndef" | "else" | "endif" if stack.is_empty() => {
+                diagnostics.push(Diagnostic::error(
+                    &token.span,
+                    format!("`#{}` without `#if`", name),
+                ));
+            }
+            "elif" | "elifdef" | "elifndef" | "else" => {
+                let conditional = stack.last_mut().unwrap();
+                conditional.active = conditional.parent_active
+                    && !conditional.taken
+                    && (name == "else" || condition(&name[2..], rest, &constants));
+                conditional.taken |= conditional.active;
+            }
+            "endif" => {
+                stack.pop();
+            }
+            _ if !active => {}
+            "define" => {
+                let (name, value) = split_define(rest);
+                //Function-like macros can only be checked with `defined`
+                let value = match value.starts_with('(') {
+                    true => String::new(),
+                    false => value.trim().to_owned(),
+                };
+                constants.macros.insert(name.to_owned(), value);
+                output.push(token);
+            }
+            "undef" => {
+                constants.macros.remove(rest.trim());
+                output.push(token);
+            }
+            _ => output.push(token),
+        }
+    }
+    for conditional in stack {
+        diagnostics.push(Diagnostic::error(
+            &conditional.span,
+            "`#if` without `#endif`, everything after it depends on the condition",
+        ));
+    }
+    output
+}
+
+//`#  ifdef NAME` is ("ifdef", "NAME")
+pub fn directive(text: &str) -> Option<(&str, &str)> {
+    let text = text.strip_prefix('#')?.trim_start();
+    let end = text
+        .find(|c: char| !c.is_alphanumeric())
+        .unwrap_or(text.len());
+    Some((&text[..end], &text[end..]))
+}
+
+//`NAME(a, b) value` is ("NAME", "(a, b) value"), the value isn't trimmed to tell the two apart
+pub fn split_define(define: &str) -> (&str, &str) {
+    let define = define.trim_start();
+    let end = define
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(define.len());
+    define.split_at(end)
+}
+
+fn condition(kind: &str, expression: &str, constants: &Constants) -> bool {
+    let name = expression.split_whitespace().next().unwrap_or_default();
+    match kind {
+        "ifdef" => constants.macros.contains_key(name),
+        "ifndef" => !constants.macros.contains_key(name),
+        _ => {
+            let tokens = replace_defined(tokenize(expression), constants);
+            //Anything we can't evaluate is treated as false
+            evaluate(&tokens, &|name| constants.value(name)).is_some_and(|v| v != 0)
+        }
+    }
+}
+
+//`defined NAME` and `defined(NAME)` become 1 or 0
+fn replace_defined(tokens: Vec<Token>, constants: &Constants) -> Vec<Token> {
+    let mut output = Vec::new();
+    let mut tokens = tokens.into_iter().filter(|t| !t.is_comment()).peekable();
+    while let Some(token) = tokens.next() {
+        if !token.is_ident("defined") {
+            output.push(token);
+            continue;
+        }
+        let parenthesized = tokens.next_if(|t| t.is_punct("(")).is_some();
+        let name = tokens.next().map(|t| t.text).unwrap_or_default();
+        if parenthesized {
+            tokens.next_if(|t| t.is_punct(")"));
+        }
+        output.push(Token {
+            kind: TokenKind::Number,
+            text: (constants.macros.contains_key(&name) as i64).to_string(),
+            span: token.span,
+        });
+    }
+    output
+}
+
+#[cfg(test)]
+fn active_identifiers(source: &str, defines: &[(&str, &str)]) -> Vec<String> {
+    let defines = defines
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    preprocess(tokenize(source), &defines, &mut Vec::new())
+        .into_iter()
+        .filter(|t| t.kind == TokenKind::Identifier)
+        .map(|t| t.text)
+        .collect()
+}
+
+#[test]
+fn ifdef_test() {
+    let source = "#ifdef A\na\n#elifdef B\nb\n#else\nc\n#endif\n#ifndef A\nnot_a\n#endif";
+    assert_eq!(active_identifiers(source, &[("A", "1")]), vec!["a"]);
+    assert_eq!(active_identifiers(source, &[("B", "")]), vec!["b", "not_a"]);
+    assert_eq!(active_identifiers(source, &[]), vec!["c", "not_a"]);
+}
+
+#[test]
+fn if_expression_test() {
+    let source = "#if 0\nzero\n#elif VERSION >= 2 && defined(EXTRA)\nnew\n\
+                  #elif VERSION == 1 || defined LEGACY\nold\n#endif";
+    assert_eq!(active_identifiers(source, &[]), Vec::<String>::new());
+    assert_eq!(active_identifiers(source, &[("LEGACY", "1")]), vec!["old"]);
+    assert_eq!(active_identifiers(source, &[("VERSION", "1")]), vec!["old"]);
+    assert_eq!(
+        active_identifiers(source, &[("VERSION", "3"), ("EXTRA", "")]),
+        vec!["new"]
+    );
+}
+
+#[test]
+fn nested_and_file_defines_test() {
+    let source = "#define FEATURE 2\n#if FEATURE > 1\n#if 0\nhidden\n#else\nshown\n#endif\n\
+                  #undef FEATURE\n#endif\n#ifdef FEATURE\nstill_defined\n#endif\n\
+                  #if 0\n#define HIDDEN\n#endif\n#ifdef HIDDEN\nhidden_define\n#endif";
+    assert_eq!(active_identifiers(source, &[]), vec!["shown"]);
+}
+
+#[test]
+fn unbalanced_conditionals_test() {
+    let mut diagnostics = Vec::new();
+    let tokens = preprocess(
+        tokenize("#endif\na\n#ifdef X\nb\n"),
+        &HashMap::new(),
+        &mut diagnostics,
+    );
+    assert_eq!(tokens.len(), 1);
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0].message, "`#endif` without `#if`");
+    assert_eq!((diagnostics[1].line, diagnostics[1].column), (3, 1));
+}
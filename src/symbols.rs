@@ -0,0 +1,310 @@
+//The GPLv3 License (GPLv3)
+//
+//Copyright (c) 2023 Ciubix8513
+//
+//This program is free software: you can redistribute it and/or modify
+//it under the terms of the GNU General Public License as published by
+//the Free Software Foundation, either version 3 of the License, or
+//any later version.
+//
+//This program is distributed in the hope that it will be useful,
+//but WITHOUT ANY WARRANTY; without even the implied warranty of
+//MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//GNU General Public License for more details.
+//
+//You should have received a copy of the GNU General Public License
+//along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    config::Config,
+    eval::Constants,
+    layout::{layout, Abi, Types},
+    parser::{
+        parse_file_with_config, DocumentationData, DocumentationItem, DocumentationItemChild,
+        DocumentationType, TypeRef,
+    },
+};
+use encoding::EncodingRef;
+use rayon::prelude::*;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+//Every parsed header and the types documented in them
+#[derive(Default, Debug)]
+pub struct SymbolTable {
+    //The files passed in, by the path they were passed with
+    files: HashMap<PathBuf, DocumentationData>,
+    //Type name -> the file and the index of the item, both the tag and the typedef name are here
+    types: HashMap<String, (PathBuf, usize)>,
+    //The documentation of the headers that were only included
+    included: HashMap<PathBuf, DocumentationData>,
+    //Why the files passed in couldn't be parsed
+    errors: HashMap<PathBuf, String>,
+    //The simple typedefs of all the files
+    typedefs: HashMap<String, DocumentationItemChild>,
+    //The undocumented structs, unions and enums of all the files, only for the layout
+    undocumented: HashMap<String, DocumentationItem>,
+    //The macros and enumerators of all the files, for the array lengths
+    constants: Constants,
+    //The struct layouts are only computed for a target
+    abi: Option<Abi>,
+}
+
+impl SymbolTable {
+    //Parses the files together with every header they include, directly or not
+    pub fn load(files: &[PathBuf], encoding: EncodingRef, config: &Config) -> SymbolTable {
+        SymbolTable::load_with(files, Vec::new(), encoding, config)
+    }
+
+    //Same as `load`, with sources that were already parsed, e.g. from stdin, under the names they were parsed with
+    pub fn load_with(
+        files: &[PathBuf],
+        sources: Vec<(PathBuf, Result<DocumentationData, io::Error>)>,
+        encoding: EncodingRef,
+        config: &Config,
+    ) -> SymbolTable {
+        let mut table = SymbolTable {
+            //Checked when the config is loaded
+            abi: config.layout.abi().ok().flatten(),
+            ..Default::default()
+        };
+        let mut parsed = files
+            .par_iter()
+            .map(|file| {
+                let data = parse_file_with_config(file.clone(), encoding, config);
+                (file.clone(), data)
+            })
+            .collect::<Vec<_>>();
+        parsed.extend(sources);
+
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::new();
+        for (file, data) in parsed {
+            //Files that can't be read are reported when they are exported
+            let data = match data {
+                Ok(data) => data,
+                Err(e) => {
+                    table.errors.insert(file, e.to_string());
+                    continue;
+                }
+            };
+            visited.insert(fs::canonicalize(&file).unwrap_or(file.clone()));
+            queue.extend(includes(&file, &data, config));
+            table.add(&file, data);
+        }
+        while let Some(file) = queue.pop_front() {
+            if !visited.insert(fs::canonicalize(&file).unwrap_or(file.clone())) {
+                continue;
+            }
+            if let Ok(data) = parse_file_with_config(file.clone(), encoding, config) {
+                queue.extend(includes(&file, &data, config));
+                table.add_included(&file, data);
+            }
+        }
+        table
+    }
+
+    pub fn add(&mut self, file: &Path, data: DocumentationData) {
+        self.register(file, &data);
+        self.files.insert(file.to_owned(), data);
+    }
+
+    //Only the types of an included header are used, it doesn't get a document of its own
+    pub fn add_included(&mut self, file: &Path, data: DocumentationData) {
+        self.register(file, &data);
+        self.included.insert(file.to_owned(), data);
+    }
+
+    //The first file to define a name wins
+    fn register(&mut self, file: &Path, data: &DocumentationData) {
+        for (name, typedef) in &data.typedefs {
+            self.typedefs
+                .entry(name.clone())
+                .or_insert_with(|| typedef.clone());
+        }
+        for (name, item) in &data.undocumented {
+            self.undocumented
+                .entry(name.clone())
+                .or_insert_with(|| item.clone());
+        }
+        for (name, value) in &data.macros {
+            self.constants
+                .macros
+                .entry(name.clone())
+                .or_insert_with(|| value.clone());
+        }
+        for (name, value) in &data.enumerators {
+            self.constants.values.entry(name.clone()).or_insert(*value);
+        }
+        for (i, item) in data.items.iter().enumerate() {
+            if !matches!(
+                item.r#type,
+                DocumentationType::Struct | DocumentationType::Enum | DocumentationType::Union
+            ) {
+                continue;
+            }
+            for name in [Some(&item.name), item.tag.as_ref(), item.alias.as_ref()]
+                .into_iter()
+                .flatten()
+            {
+                self.types
+                    .entry(name.clone())
+                    .or_insert((file.to_owned(), i));
+            }
+        }
+    }
+
+    //The documentation of a file passed to `load` or `add`
+    pub fn data(&self, file: &Path) -> Option<&DocumentationData> {
+        self.files.get(file)
+    }
+
+    //Why a file passed to `load` has no data
+    pub fn error(&self, file: &Path) -> Option<&str> {
+        self.errors.get(file).map(String::as_str)
+    }
+
+    //`MsgHeader`, `struct msg_header` and `const MsgHeader` all work
+    pub fn lookup(&self, type_name: &str) -> Option<(&Path, &DocumentationItem)> {
+        let name = type_name
+            .split_whitespace()
+            .rfind(|w| !matches!(*w, "struct" | "union" | "enum" | "const" | "volatile"))?;
+        let (file, index) = self.types.get(name)?;
+        let data = self.files.get(file).or_else(|| self.included.get(file))?;
+        Some((file, &data.items[*index]))
+    }
+
+    //Points the fields of the data to the items documenting their types, and lays the structs out if there is an ABI
+    pub fn resolve(&self, data: &mut DocumentationData) {
+        for item in data.items.iter_mut() {
+            self.resolve_children(&mut item.children);
+            if let Some(abi) = &self.abi {
+                layout(item, abi, self);
+            }
+        }
+    }
+
+    fn resolve_children(&self, children: &mut [DocumentationItemChild]) {
+        for child in children {
+            child.type_ref = self.lookup(&child.base_type).map(|(file, item)| TypeRef {
+                name: item.name.clone(),
+                r#type: item.r#type.clone(),
+                file: file.to_owned(),
+            });
+            if let Some(nested) = child.nested.as_mut() {
+                self.resolve_children(&mut nested.children);
+            }
+        }
+    }
+}
+
+impl Types for SymbolTable {
+    fn item(&self, name: &str) -> Option<&DocumentationItem> {
+        match self.lookup(name) {
+            Some((_, item)) => Some(item),
+            None => self.undocumented.get(name),
+        }
+    }
+
+    fn typedef(&self, name: &str) -> Option<&DocumentationItemChild> {
+        self.typedefs.get(name)
+    }
+
+    fn evaluate(&self, expression: &str) -> Option<i64> {
+        self.constants.evaluate_str(expression)
+    }
+}
+
+//The included headers that could be found
+fn includes(file: &Path, data: &DocumentationData, config: &Config) -> Vec<PathBuf> {
+    data.includes
+        .iter()
+        .filter_map(|include| find_include(include, file, &config.preprocessor.include_paths))
+        .collect()
+}
+
+//`"a.h"` is looked up next to the including file first, `<a.h>` only in the include paths
+pub fn find_include(include: &str, from: &Path, include_paths: &[PathBuf]) -> Option<PathBuf> {
+    let (name, local) = if let Some(name) = include.strip_prefix('"') {
+        (name.strip_suffix('"')?, true)
+    } else {
+        (include.strip_prefix('<')?.strip_suffix('>')?, false)
+    };
+    let directory = from.parent().map(Path::to_path_buf).unwrap_or_default();
+    local
+        .then_some(directory)
+        .into_iter()
+        .chain(include_paths.iter().cloned())
+        .map(|directory| directory.join(name))
+        .find(|path| path.is_file())
+}
+
+#[test]
+fn find_include_test() {
+    let from = PathBuf::from("test_data/test17.h");
+    let include_paths = [PathBuf::from("test_data/include")];
+    assert_eq!(
+        find_include("\"test1.h\"", &from, &[]),
+        Some(PathBuf::from("test_data/test1.h"))
+    );
+    assert_eq!(find_include("<test1.h>", &from, &[]), None);
+    assert_eq!(
+        find_include("<msg_header.h>", &from, &include_paths),
+        Some(PathBuf::from("test_data/include/msg_header.h"))
+    );
+    assert_eq!(find_include("<stdint.h>", &from, &include_paths), None);
+}
+
+#[test]
+fn cross_file_types_test() {
+    let mut config = Config::default();
+    config
+        .preprocessor
+        .include_paths
+        .push(PathBuf::from("test_data/include"));
+    let file = PathBuf::from("test_data/test17.h");
+    let table = SymbolTable::load(std::slice::from_ref(&file), encoding::all::UTF_8, &config);
+
+    let (header_file, header) = table.lookup("struct msg_header").unwrap();
+    assert_eq!(header_file, Path::new("test_data/include/msg_header.h"));
+    assert_eq!(header.name, "MsgHeader");
+    //Included through msg_header.h
+    assert_eq!(
+        table.lookup("MsgKind").unwrap().1.r#type,
+        DocumentationType::Enum
+    );
+
+    let mut data = table.data(&file).unwrap().clone();
+    table.resolve(&mut data);
+    let children = &data.items[0].children;
+    assert_eq!(children[0].type_ref.as_ref().unwrap().name, "MsgHeader");
+    assert_eq!(children[1].type_ref.as_ref().unwrap().name, "Point");
+    assert_eq!(children[2].type_ref, None);
+    //Included headers don't get documents of their own
+    assert!(table
+        .data(Path::new("test_data/include/msg_header.h"))
+        .is_none());
+}
+
+#[test]
+fn load_parsed_sources_test() {
+    use crate::parser::parse_str;
+    let name = PathBuf::from("test_data/stdin.h");
+    let source = "#include \"include/msg_header.h\"\n\
+                  //! Message\n\
+                  typedef struct {\n\
+                    MsgHeader header; //!< Header\n\
+                  } Message;";
+    let sources = vec![(name.clone(), Ok(parse_str(source, &name)))];
+    let table = SymbolTable::load_with(&[], sources, encoding::all::UTF_8, &Config::default());
+    let mut data = table.data(&name).unwrap().clone();
+    table.resolve(&mut data);
+    //The include is looked up next to the name
+    assert_eq!(
+        data.items[0].children[0].type_ref.as_ref().unwrap().file,
+        PathBuf::from("test_data/include/msg_header.h")
+    );
+}
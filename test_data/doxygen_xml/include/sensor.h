@@ -0,0 +1,50 @@
+#include <stdint.h>
+
+/** API version */
+#define SENSOR_API_VERSION 2
+
+typedef uint16_t word_t;
+
+/** @defgroup sensor Sensors
+ *  Sensor access
+ *  @{ */
+#define SENSOR_COUNT 4 /**< Number of sensors */
+
+/** Sensor kind */
+enum sensor_kind {
+  SENSOR_TEMP,                        /**< Temperature */
+  SENSOR_PRESSURE = SENSOR_COUNT + 1, /**< Pressure */
+  SENSOR_HUMIDITY                     /**< Humidity */
+};
+
+/** Sensor reading */
+typedef struct reading_s {
+  uint8_t id;                   //!< [ID] Sensor id
+  int16_t values[SENSOR_COUNT]; //!< Values //(unit: mV)
+  unsigned int flags : 3;
+  struct {
+    int x; //!< X
+    int y; //!< Y
+  } pos; //!< Position
+} reading_t;
+
+/** Reads a sensor. Blocks until the conversion is done.
+ *  @param[in] id Sensor id
+ *  @param[out] out Where the `reading` goes
+ *  @return 0 on success
+ *  @note Not reentrant */
+int read_sensor(uint8_t id, reading_t *out);
+
+/** @} */
+
+/** Sampling mode */
+typedef enum {
+  MODE_ONESHOT,   /**< Single conversion */
+  MODE_CONTINUOUS /**< Free running */
+} mode_t;
+
+/** Calibration */
+typedef struct {
+  int16_t offset; /**< Offset */
+  uint16_t gain;  /**< Gain */
+} calibration_t;
@@ -0,0 +1,12 @@
+#ifndef MSG_HEADER_H
+#define MSG_HEADER_H
+
+#include "msg_kind.h"
+
+//! Common message header
+typedef struct msg_header {
+  MsgKind kind; //!< Kind of the message
+  int length;   //!< Length of the payload
+} MsgHeader;
+
+#endif
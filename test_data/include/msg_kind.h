@@ -0,0 +1,10 @@
+#ifndef MSG_KIND_H
+#define MSG_KIND_H
+
+//! Message kinds
+typedef enum {
+  MSG_DATA, //!< Data
+  MSG_ACK,  //!< Acknowledgement
+} MsgKind;
+
+#endif
@@ -0,0 +1,23 @@
+/**
+ * Javadoc style struct
+ */
+typedef struct javadoc_s {
+  int a; /**< This is A */
+  int b; ///< This is B
+  int c; /*!< This is C,
+              continued */
+  int d; //!< This is D
+         //!< continued
+} javadoc_t;
+
+/// Slashes style enum
+/// with a second line
+typedef enum slashes_e {
+  A, ///< This is A
+  B  /*!< This is B */
+} slashes_t;
+
+/*! Qt style struct */
+typedef struct qt_s {
+  int a; /*!< This is A */
+} qt_t;
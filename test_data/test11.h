@@ -0,0 +1,10 @@
+/**
+ * @brief Status word
+ * @details Sent once per second.
+ * @since 2.1
+ */
+typedef struct status_s {
+  int mode; //!< @brief Operating mode @note Only modes 0..3 are used
+  int temp; //!< Temperature @warning Not calibrated
+  int old;  //!< Old field @deprecated Use mode instead
+} status_t;
@@ -0,0 +1,35 @@
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/**
+ * @brief Sends a message
+ * @param[in] id Message id
+ * @param[out] reply Where the reply is stored
+ * @return 0 on success
+ */
+int send_message(unsigned int id, struct reply_s *reply);
+
+//! Resets the device
+extern void reset(void);
+
+//! Callback, not a function
+void (*callback)(int);
+
+//! Inline helper
+static inline int twice(int x) { return x * 2; }
+
+//! Creates a message
+struct message_s *make_message(void);
+
+//! Returns the current mode
+enum mode get_mode(void);
+
+void undocumented(void);
+
+//! Initialised by a call
+int x = foo(1, 2);
+
+#ifdef __cplusplus
+}
+#endif
@@ -0,0 +1,17 @@
+#define BASE 0x10
+#define SHIFT 2
+
+//! Message kinds
+typedef enum {
+  KIND_NONE,             //!< Nothing
+  KIND_FIRST = BASE,     //!< The first kind
+  KIND_SECOND,           //!< The second kind
+  KIND_HIDDEN,
+  KIND_AFTER_HIDDEN,     //!< Counts the undocumented one too
+  KIND_CHAR = 'A',       //!< Char literal
+  KIND_OCTAL = 010,      //!< Octal literal
+  KIND_FLAGS = 1 << SHIFT | 1, //!< Shifted flags
+  KIND_LAST = KIND_SECOND + KIND_FLAGS, //!< Refers to earlier enumerators
+  KIND_UNKNOWN = UNDEFINED_THING, //!< Can't be computed
+  KIND_NEXT,             //!< Neither can this one
+} kind_t;
@@ -0,0 +1,7 @@
+//! Navigation data
+typedef struct {
+  int latitude;  //!< [LAT] Latitude // (min:-324000 max:324000 signed:4 bits:5..19 lsb:0.001)
+  int altitude;  //!< [ALT] Altitude // (signed:yes bits:0..15 msb:16384)
+  int speed;     //!< [SPD] Speed // (bits:0..7 lsb:0.5)
+  int heading;   //!< [HDG] Heading // (min:0 max:360)
+} nav_t;
@@ -0,0 +1,6 @@
+//! Wheel data
+typedef struct {
+  int speed;   //!< [SPD] Speed // (unit: m/s default:0 bits:0..15 range:0..300)
+  int counter; //!< [CNT] Counter // (endian:little lsb:1 bits:16..31)
+  int mode;    //!< [MOD] Mode // (see ICD owner:nav-team)
+} wheel_t;
@@ -0,0 +1,38 @@
+#ifndef TEST16_H
+#define TEST16_H
+
+#if 0
+//! Never compiled
+typedef struct {
+  int a; //!< A
+} disabled_t;
+#endif
+
+#ifdef LEGACY_PROTOCOL
+//! Old header
+typedef struct {
+  int id; //!< Id
+} header_t;
+#elif VARIANT >= 2
+//! New header
+typedef struct {
+  int id;    //!< Id
+  int flags; //!< Flags
+} header_t;
+#else
+//! Basic header
+typedef struct {
+  short id; //!< Id
+} header_t;
+#endif
+
+//! Commands
+enum command {
+  CMD_RESET,    //!< Reset
+#if VARIANT >= 2
+  CMD_EXTENDED, //!< Only in the new variant
+#endif
+  CMD_LAST,     //!< Last
+};
+
+#endif
@@ -0,0 +1,15 @@
+#include <msg_header.h>
+#include <stdint.h>
+
+//! Message
+typedef struct {
+  MsgHeader hdr;      //!< Header
+  struct point position; //!< Position
+  uint32_t checksum;  //!< Checksum
+} Message;
+
+//! A point
+typedef struct point {
+  int x; //!< X
+  int y; //!< Y
+} Point;
@@ -0,0 +1,30 @@
+//! Fine, but with an undocumented field
+typedef struct {
+  int a; //!< A
+  int b;
+  int c; //!< C // (bits:x..y default:1)
+  int d; //!< D // (min:0 max:10
+} first_t;
+
+//! Missing semicolon
+typedef struct {
+  int a; //!< A
+} second_t
+
+//! Missing closing brace
+typedef struct third_s {
+  int a; //!< A
+
+//! Still parsed
+typedef struct {
+  int a; //!< A
+} fourth_t;
+
+typedef struct {
+  int undocumented;
+} ignored_t;
+
+//! Open at the end of the file
+struct fifth_s {
+  int a; //!< A
+  int b; //!< B
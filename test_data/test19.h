@@ -0,0 +1,16 @@
+//! Packet size
+#define PACKET_SIZE \
+  (16 + 4)
+
+//! Packet
+typedef struct packet_s {
+  unsigned int id; //!< Id
+  struct {
+    int x; //!< X
+  } pos; //!< Position
+  unsigned char
+    payload[PACKET_SIZE]; //!< Payload
+} packet_t;
+
+//! Sends a packet
+int send(const packet_t *packet);
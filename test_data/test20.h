@@ -0,0 +1,5 @@
+//!  
+typedef struct {
+  unsigned short length; //!<    
+  unsigned short kind; //!<  
+} header_t;
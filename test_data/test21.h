@@ -0,0 +1,5 @@
+//! Broken
+typedef struct {
+  int a; //!< Fine
+  int b; //!< Bad  bytes
+} broken_t;
@@ -0,0 +1,69 @@
+#include <stdint.h>
+
+#define NAME_LEN 7
+
+typedef uint16_t word_t;
+typedef void (*handler_t)(int);
+
+//! Mixed fields
+typedef struct {
+  uint8_t kind;      //!< Kind
+  uint32_t value;    //!< Value
+  uint8_t flags;
+  uint16_t a : 3;    //!< A
+  uint16_t b : 14;   //!< B
+  char name[NAME_LEN + 1]; //!< Name
+  word_t word;       //!< Word
+  handler_t handler; //!< Handler
+  double ratio;      //!< Ratio
+} mixed_t;
+
+#pragma pack(push, 1)
+//! Packed by a pragma
+typedef struct {
+  uint8_t kind;   //!< Kind
+  uint32_t value; //!< Value
+} pragma_t;
+#pragma pack(pop)
+
+//! Packed by an attribute
+typedef struct __attribute__((packed)) {
+  uint8_t kind;   //!< Kind
+  uint16_t value; //!< Value
+} attribute_t;
+
+//! Overlay
+typedef union {
+  uint32_t word; //!< Word
+  struct {
+    uint8_t low;   //!< Low
+    uint16_t high; //!< High
+  } parts; //!< Parts
+} overlay_t;
+
+//! Unknown type
+typedef struct {
+  uint8_t kind;   //!< Kind
+  mystery_t more; //!< More
+} unknown_t;
+
+typedef unsigned short uint24_t;
+
+//! Own fixed width typedef
+typedef struct {
+  uint24_t wide; //!< Wide
+  uint8_t kind;  //!< Kind
+} own_t;
+
+typedef struct {
+  int x;
+} inner_t;
+
+typedef enum { MODE_A, MODE_B } mode_t;
+
+//! Undocumented member types
+typedef struct {
+  inner_t inner; //!< Inner
+  mode_t mode;   //!< Mode
+  uint8_t kind;  //!< Kind
+} outer_t;
@@ -0,0 +1,57 @@
+//! Protocol version
+#define VERSION 3
+
+/**
+ * @defgroup protocol Protocol
+ * Messages exchanged with the device
+ * @{
+ */
+
+//! Message header
+typedef struct {
+  int id; //!< Id
+} header_t;
+
+/** @defgroup commands Commands
+ *  @{ */
+
+//! Command
+typedef struct {
+  int code; //!< Code
+} command_t;
+
+//! Number of commands
+#define CMD_MAX 16
+
+/** @} */
+
+/** @} */
+
+/** @defgroup replies Replies
+ *  @ingroup protocol
+ */
+
+/**
+ * @ingroup replies
+ * Reply
+ */
+typedef struct {
+  int status; //!< Status
+} reply_t;
+
+/** @addtogroup replies
+ *  @{
+ */
+//! Sends a reply
+int send(const reply_t *reply);
+/** @} */
+
+//! @ingroup external
+//! Extra
+typedef struct {
+  int value; //!< Value
+} extra_t;
+
+/** @defgroup empty Empty */
+
+//@}
@@ -0,0 +1,26 @@
+//! Leading comments
+typedef struct {
+  //! Above, slashes
+  int a;
+  /**
+   * Above, javadoc
+   * on two lines
+   */
+  int b;
+  /** Above */
+  int c; //!< After
+  int d; //!< [D1] Only after
+  /// Above, three slashes
+  /// @note Careful
+  int e;
+  int f;
+  /** Message length */
+  unsigned short len; //!< [LEN] Length // (signed:нет bits:16)
+} leading_t;
+
+//! Leading enum comments
+enum mode {
+  //! Off
+  MODE_OFF,
+  MODE_ON, //!< On
+};
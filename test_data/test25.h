@@ -0,0 +1,19 @@
+/** @file
+ * Message definitions.
+ */
+
+/** Protocol definitions. */
+
+/**
+ * Message header. Sent before every message
+ * on the bus.
+ */
+//! The id is unique per device.
+//! Ids below 16 are reserved.
+typedef struct {
+  int id; //!< Id
+} header_t;
+
+//! @brief Sends a message. Blocks.
+//! Retries three times.
+int send(int id);
@@ -0,0 +1,16 @@
+//! Test struct
+typedef struct test
+{
+  /* This comment has a } in it */
+  int a; //!< This is A
+  unsigned long
+    b; //!< This is B
+}
+test;
+
+//! Test enum
+typedef enum test_enum
+{
+  a = 1, //!< This is A
+  b //!< This is B
+} test_enum;
@@ -0,0 +1,24 @@
+struct forward;
+
+//! Plain struct
+struct plain {
+  int a; //!< This is A
+};
+
+//! Anonymous struct
+typedef struct {
+  int a; //!< This is A
+} anonymous_t;
+
+//! Tagged struct
+typedef struct tagged_s {
+  int a; //!< This is A
+} tagged_t, *tagged_ptr;
+
+//! Plain enum
+enum plain_enum {
+  a, //!< This is A
+};
+
+//! Variable of an undocumented type
+struct forward instance;
@@ -0,0 +1,9 @@
+//! Raw word overlay
+typedef union word_u {
+  unsigned int raw; //!< Raw word
+  struct {
+    unsigned int low : 16;
+    unsigned int high : 16;
+  } halves; //!< Word halves
+  unsigned char bytes[4]; //!< Word bytes
+} word_t;
@@ -0,0 +1,13 @@
+//! Message
+typedef struct message_s {
+  //! Message header
+  struct header_s {
+    unsigned int id : 8; //!< Message id
+    unsigned int flags : 8; //!< Flags
+  } header; //!< Header
+  union {
+    unsigned int raw; //!< Raw payload
+    float value; //!< Payload as a float
+  };
+  int checksum; //!< Checksum
+} message_t;